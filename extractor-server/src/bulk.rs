@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use news_extractor::{parse_html_with_rules, Feature, SiteRules};
+use serde::Deserialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::error::Error;
+use crate::render::render;
+
+
+/// One line of the NDJSON input. Records without `render_html` are rendered
+/// with the render server.
+#[derive(Deserialize)]
+struct Record {
+    url: String,
+    render_html: Option<String>,
+}
+
+/// The url of a record and what parsing it gave.
+type Parsed = (String, Result<Feature, Error>);
+
+/// Where to render records that come without html.
+pub struct RenderConfig {
+    pub api: String,
+    pub timeout: Duration,
+}
+
+/// Counts of a bulk run.
+#[derive(Default, Debug)]
+pub struct Summary {
+    pub parsed: usize,
+    pub failed: usize,
+    pub kinds: BTreeMap<&'static str, usize>,
+    /// Most records in flight or waiting to be written at once.
+    pub max_pending: usize,
+}
+
+impl Summary {
+    pub fn print(&self) {
+        eprintln!("parsed: {}, failed: {}", self.parsed, self.failed);
+        for (kind, count) in &self.kinds {
+            eprintln!("  {}: {}", kind, count);
+        }
+    }
+}
+
+async fn parse_record(line: String, rules: Arc<SiteRules>, render_config: Arc<RenderConfig>) -> Parsed {
+    let record = match serde_json::from_str::<Record>(&line) {
+        Ok(record) => record,
+        Err(err) => return (String::new(), Err(Error::BadRequest(format!("invalid record: {}", err)))),
+    };
+    let url = record.url.clone();
+    if url.is_empty() {
+        return (url, Err(Error::BadRequest("not exists url".into())));
+    }
+    let html = match record.render_html {
+        Some(html) => html,
+        None => match render(&url, &render_config.api, render_config.timeout).await {
+            Ok(html) => html,
+            Err(err) => return (url, Err(err)),
+        },
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        parse_html_with_rules(record.url, &mut html.as_bytes(), None, &rules).map_err(Error::from)
+    }).await.unwrap_or_else(|e| Err(Error::Internal(format!("parse task failed: {}", e))));
+    (url, result)
+}
+
+fn report<W: Write>(line_no: usize, (url, result): Parsed, output: &mut W, summary: &mut Summary) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Ok(feature) => {
+            serde_json::to_writer(&mut *output, &feature)?;
+            output.write_all(b"\n")?;
+            summary.parsed += 1;
+        },
+        Err(err) => {
+            eprintln!("line {}: {}: {}", line_no, url, err);
+            summary.failed += 1;
+            *summary.kinds.entry(err.kind()).or_default() += 1;
+        },
+    }
+    Ok(())
+}
+
+async fn finish(line_no: usize, task: JoinHandle<Parsed>) -> (usize, Parsed) {
+    let parsed = task.await.unwrap_or_else(|e| (String::new(), Err(Error::Internal(format!("parse task failed: {}", e)))));
+    (line_no, parsed)
+}
+
+/// Parses NDJSON records from `input` with `workers` records in flight and
+/// writes one JSON `Feature` per line to `output`, in input order. Failed
+/// records, including lines that are not UTF-8 or not JSON, are reported on
+/// stderr and counted in the returned summary.
+pub async fn parse_ndjson<R, W>(mut input: R, output: &mut W, workers: usize, rules: SiteRules, render_config: RenderConfig)
+    -> Result<Summary, Box<dyn std::error::Error>>
+where R: AsyncBufRead + Unpin, W: Write {
+    let sem = Arc::new(Semaphore::new(workers));
+    let rules = Arc::new(rules);
+    let render_config = Arc::new(render_config);
+    let mut pending: VecDeque<(usize, JoinHandle<Parsed>)> = VecDeque::new();
+    let mut summary = Summary::default();
+    let mut buf = vec![];
+    let mut line_no = 0;
+    loop {
+        buf.clear();
+        if input.read_until(b'\n', &mut buf).await? == 0 {
+            break;
+        }
+        line_no += 1;
+        let line = match String::from_utf8(std::mem::take(&mut buf)) {
+            Ok(line) => line,
+            Err(_) => {
+                let err = Err(Error::BadRequest("invalid record: not utf-8".to_string()));
+                pending.push_back((line_no, tokio::spawn(async move { (String::new(), err) })));
+                continue;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let permit = sem.clone().acquire_owned().await.unwrap();
+        let (rules, render_config) = (rules.clone(), render_config.clone());
+        pending.push_back((line_no, tokio::spawn(async move {
+            let _permit = permit;
+            parse_record(line, rules, render_config).await
+        })));
+        summary.max_pending = summary.max_pending.max(pending.len());
+        // Bounds the finished results held back to keep the output in order.
+        while pending.len() > workers * 2 {
+            let (line_no, task) = pending.pop_front().unwrap();
+            let (line_no, parsed) = finish(line_no, task).await;
+            report(line_no, parsed, output, &mut summary)?;
+        }
+    }
+    for (line_no, task) in pending {
+        let (line_no, parsed) = finish(line_no, task).await;
+        report(line_no, parsed, output, &mut summary)?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use news_extractor::SiteRules;
+
+    use super::{parse_ndjson, RenderConfig};
+
+    fn render_config() -> RenderConfig {
+        RenderConfig { api: "http://127.0.0.1:9/render".to_string(), timeout: Duration::from_secs(1) }
+    }
+
+    fn record(i: usize) -> String {
+        format!(r#"{{"url":"https://example.com/{}","render_html":"<html><head><title>Page {}</title></head><body><p>Text</p></body></html>"}}"#, i, i)
+    }
+
+    #[tokio::test]
+    async fn test_in_order_and_bounded() {
+        let input = (0..50).map(record).collect::<Vec<_>>().join("\n");
+        let mut output = vec![];
+        let summary = parse_ndjson(input.as_bytes(), &mut output, 3, SiteRules::default(), render_config()).await.unwrap();
+        let titles = String::from_utf8(output).unwrap().lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["title"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, (0..50).map(|i| format!("Page {}", i)).collect::<Vec<_>>());
+        assert_eq!((summary.parsed, summary.failed), (50, 0));
+        assert!(summary.max_pending <= 3 * 2 + 1);
+    }
+
+    #[tokio::test]
+    async fn test_bad_lines() {
+        let mut input = format!("{}\nnot json\n", record(1)).into_bytes();
+        input.extend(b"\xff\xfe\n\n");
+        input.extend(record(2).as_bytes());
+        let mut output = vec![];
+        let summary = parse_ndjson(input.as_slice(), &mut output, 2, SiteRules::default(), render_config()).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+        assert_eq!((summary.parsed, summary.failed), (2, 2));
+        assert_eq!(summary.kinds.get("bad_request"), Some(&2));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+use axum::http::StatusCode;
+
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request is malformed, e.g. it has no url or no html to parse.
+    BadRequest(String),
+    /// The render server could not be reached or returned an unusable response.
+    Render(String),
+    /// An `/admin` request without the admin token.
+    Unauthorized(String),
+    /// The render server did not answer in time.
+    Timeout(String),
+    /// The html was fetched but no article could be extracted from it.
+    Extract(news_extractor::Error),
+    Internal(String),
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::BadRequest(_) => "bad_request",
+            Error::Render(_) => "render_error",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Timeout(_) => "timeout",
+            Error::Extract(err) => err.kind(),
+            Error::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Render(_) => StatusCode::BAD_GATEWAY,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Extract(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadRequest(msg) => write!(f, "bad request: {}", msg),
+            Error::Render(msg) => write!(f, "render failed: {}", msg),
+            Error::Unauthorized(msg) => write!(f, "unauthorized: {}", msg),
+            Error::Timeout(msg) => write!(f, "timeout: {}", msg),
+            Error::Extract(err) => write!(f, "extract failed: {}", err),
+            Error::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Extract(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<news_extractor::Error> for Error {
+    fn from(err: news_extractor::Error) -> Self {
+        Error::Extract(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Error::Timeout(err.to_string())
+        } else {
+            Error::Render(err.to_string())
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use news_extractor::{find_page_url, parse_html_with_rules, SiteRules};
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::parser::print_result;
+
+
+/// The url of a saved page in a `page.json` or `page.html.json` sidecar.
+#[derive(Deserialize)]
+struct Sidecar {
+    url: String,
+}
+
+fn sidecar_url(path: &Path) -> Option<String> {
+    let mut candidates = vec![path.with_extension("json")];
+    if let Some(name) = path.file_name() {
+        candidates.push(path.with_file_name(format!("{}.json", name.to_string_lossy())));
+    }
+    candidates.iter()
+        .filter_map(|sidecar| fs::read_to_string(sidecar).ok())
+        .filter_map(|s| serde_json::from_str::<Sidecar>(&s).ok())
+        .map(|sidecar| sidecar.url.trim().to_string())
+        .find(|url| !url.is_empty())
+}
+
+/// Loads a `--url-map`: a JSON object from file names, relative to `--dir` or
+/// bare, to page urls.
+fn load_url_map(path: Option<&str>) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    match path {
+        Some(path) => {
+            let s = fs::read_to_string(path).map_err(|err| format!("read {} failed: {}", path, err))?;
+            Ok(serde_json::from_str(&s).map_err(|err| format!("parse {} failed: {}", path, err))?)
+        },
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// The url of a saved page: `--url`, then its sidecar JSON, then the url map,
+/// then the canonical url declared in the page.
+fn page_url(path: &Path, name: &str, html: &[u8], url: Option<&str>, url_map: &HashMap<String, String>) -> Option<String> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    url.map(str::to_string)
+        .or_else(|| sidecar_url(path))
+        .or_else(|| url_map.get(name).or_else(|| url_map.get(&file_name)).cloned())
+        .or_else(|| find_page_url(html))
+}
+
+/// Collects the pages under `dir`. Symlinked directories are not followed, so
+/// a link loop cannot recurse forever; symlinked pages are kept.
+fn html_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            html_files(&path, files)?;
+        } else if file_type.is_symlink() && path.is_dir() {
+            continue;
+        } else if matches!(path.extension().and_then(|ext| ext.to_str()), Some("html" | "htm")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn parse_file(path: &Path, name: &str, url: Option<&str>, url_map: &HashMap<String, String>, rules: &SiteRules) -> (String, Result<news_extractor::Feature, Error>) {
+    let html = match fs::read(path) {
+        Ok(html) => html,
+        Err(err) => return (String::new(), Err(Error::BadRequest(format!("read {} failed: {}", name, err)))),
+    };
+    match page_url(path, name, &html, url, url_map) {
+        Some(url) => (url.clone(), parse_html_with_rules(url, &mut html.as_slice(), None, rules).map_err(Error::from)),
+        None => (String::new(), Err(Error::BadRequest(format!("no url for {}: pass --url, a sidecar json or --url-map, or add <link rel=canonical>", name)))),
+    }
+}
+
+/// Parses saved pages from `--file` or `--dir` without a render server. Fails
+/// when any page does, after printing the others.
+pub fn main_files(arg: &ArgMatches, rules: &SiteRules) -> Result<(), Box<dyn std::error::Error>> {
+    let output = arg.value_of("output").unwrap_or("text");
+    let url_map = load_url_map(arg.value_of("url-map"))?;
+    if let Some(file) = arg.value_of("file") {
+        let (url, result) = parse_file(Path::new(file), file, arg.value_of("url"), &url_map, rules);
+        return print_result(url, None, result, output);
+    }
+    let dir = Path::new(arg.value_of("dir").unwrap());
+    let mut files = vec![];
+    html_files(dir, &mut files).map_err(|err| format!("read {} failed: {}", dir.display(), err))?;
+    files.sort();
+    let (mut parsed, mut failed) = (0, 0);
+    for path in files {
+        let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().to_string();
+        let (url, result) = parse_file(&path, &name, None, &url_map, rules);
+        match print_result(url, Some(&name), result, output) {
+            Ok(()) => parsed += 1,
+            Err(err) => {
+                eprintln!("{}: {}", name, err);
+                failed += 1;
+            },
+        }
+    }
+    eprintln!("parsed: {}, failed: {}", parsed, failed);
+    if failed > 0 {
+        return Err(format!("{} of {} pages failed", failed, parsed + failed).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use super::page_url;
+
+    #[test]
+    fn test_page_url_precedence() {
+        let dir = std::env::temp_dir().join(format!("extractor-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let page = dir.join("page.html");
+        let html = br#"<html><head><link rel="canonical" href="https://canonical.com/page"></head><body></body></html>"#;
+        fs::write(&page, html).unwrap();
+        let url_map = HashMap::from([("page.html".to_string(), "https://mapped.com/page".to_string())]);
+
+        assert_eq!(page_url(&page, "page.html", html, None, &HashMap::new()).as_deref(), Some("https://canonical.com/page"));
+        assert_eq!(page_url(&page, "page.html", html, None, &url_map).as_deref(), Some("https://mapped.com/page"));
+        fs::write(dir.join("page.html.json"), r#"{"url": "https://sidecar.com/page"}"#).unwrap();
+        assert_eq!(page_url(&page, "page.html", html, None, &url_map).as_deref(), Some("https://sidecar.com/page"));
+        assert_eq!(page_url(&page, "page.html", html, Some("https://given.com/page"), &url_map).as_deref(), Some("https://given.com/page"));
+        fs::remove_file(dir.join("page.html.json")).unwrap();
+        assert_eq!(page_url(&page, "page.html", b"<html></html>", None, &HashMap::new()), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
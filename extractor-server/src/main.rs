@@ -1,11 +1,17 @@
+mod bulk;
+mod error;
+mod files;
 mod render;
 mod parser;
+mod rules;
 mod server;
+mod warc;
 
 use clap::Arg;
 use clap::Command;
 use parser::main_parser;
 use server::main_server;
+use warc::main_warc;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -15,18 +21,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .subcommand(Command::new("parse")
             .arg(Arg::new("render-server").long("--render-server").short('r')
                 .default_value("http://localhost:3000/render"))
+            .arg(Arg::new("render-timeout").long("--render-timeout").default_value("30")
+                .help("render server timeout in seconds"))
             .arg(Arg::new("stdin").takes_value(false))
-            .arg(Arg::new("url").long("-url").short('u').required(true).takes_value(true))
+            .arg(Arg::new("url").long("-url").short('u').required_unless_present_any(["ndjson", "file", "dir"]).takes_value(true))
+            .arg(Arg::new("ndjson").long("--ndjson").takes_value(true)
+                .help("parse {\"url\", \"render_html\"} records from an NDJSON file, or - for stdin"))
+            .arg(Arg::new("file").long("--file").takes_value(true).conflicts_with_all(&["dir", "ndjson"])
+                .help("parse a saved html page instead of rendering the url"))
+            .arg(Arg::new("dir").long("--dir").takes_value(true).conflicts_with("ndjson")
+                .help("parse every .html/.htm page under a directory"))
+            .arg(Arg::new("url-map").long("--url-map").takes_value(true)
+                .help("JSON object mapping page file names to their urls"))
+            .arg(Arg::new("workers").long("--workers").short('w').takes_value(true)
+                .help("records parsed at a time with --ndjson, default the number of cpus"))
+            .arg(Arg::new("rules").long("--rules").takes_value(true)
+                .help("site rule file or directory of .toml/.json rule files"))
+            .arg(Arg::new("output").long("--output").short('o').default_value("text")
+                .possible_values(["json", "json-pretty", "text", "markdown", "html"]))
+        ).subcommand(Command::new("warc")
+            .about("extract the html responses of a (gzipped) WARC archive as JSON lines")
+            .arg(Arg::new("path").required(true).help("WARC file, or - for stdin"))
+            .arg(Arg::new("rules").long("--rules").takes_value(true)
+                .help("site rule file or directory of .toml/.json rule files"))
         ).subcommand(Command::new("server")
             .arg(Arg::new("host").long("--host").default_value("0.0.0.0"))
             .arg(Arg::new("port").long("--port").short('p').default_value("8080"))
             .arg(Arg::new("concurrency").long("--concurrency").short('c').default_value("4"))
+            .arg(Arg::new("render-timeout").long("--render-timeout").default_value("30")
+                .help("render server timeout in seconds"))
+            .arg(Arg::new("rules-dir").long("--rules-dir").takes_value(true)
+                .help("directory of .toml/.json site rule files, reloaded on change and on SIGHUP"))
+            .arg(Arg::new("rules-poll").long("--rules-poll").default_value("5")
+                .help("seconds between checks of the rules dir for changes"))
+            .arg(Arg::new("admin-token").long("--admin-token").takes_value(true)
+                .help("serve /admin/rules, requiring `Authorization: Bearer <token>`; also read from EXTRACTOR_ADMIN_TOKEN"))
         );
     let matches = app.get_matches();
     if let Some((cmd, matches)) = matches.subcommand() {
         match cmd {
             "server" => main_server(matches).await,
             "parse" => main_parser(matches).await,
+            "warc" => main_warc(matches),
             _ => Err(format!("not found subcommand: {}", cmd).into())
         }
     } else {
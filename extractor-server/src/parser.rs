@@ -1,22 +1,126 @@
+use std::time::Duration;
+
 use clap::ArgMatches;
-use news_extractor::parse_html;
+use news_extractor::{parse_html_with_rules, Feature, SiteRules};
+use serde::Serialize;
+use tokio::io::BufReader;
 
+use crate::bulk::{parse_ndjson, RenderConfig};
+use crate::error::Error;
+use crate::files::main_files;
 use crate::render::render;
 
 pub async fn main_parser(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
-    let url = arg.value_of("url").unwrap().to_string();
     let api = arg.value_of("render-server").unwrap();
-    let feature = if arg.is_present("stdin") {
+    let timeout = Duration::from_secs(arg.value_of("render-timeout").unwrap().parse::<u64>()?);
+    let rules = match arg.value_of("rules") {
+        Some(path) => SiteRules::load(path)?,
+        None => SiteRules::default(),
+    };
+    if let Some(path) = arg.value_of("ndjson") {
+        if arg.occurrences_of("output") > 0 && arg.value_of("output") != Some("json") {
+            return Err("--ndjson writes json lines; --output other than json is not supported with it".into());
+        }
+        let workers = match arg.value_of("workers") {
+            Some(workers) => workers.parse::<usize>()?.max(1),
+            None => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        };
+        let render_config = RenderConfig { api: api.to_string(), timeout };
+        let mut stdout = std::io::stdout().lock();
+        let summary = if path == "-" {
+            parse_ndjson(BufReader::new(tokio::io::stdin()), &mut stdout, workers, rules, render_config).await?
+        } else {
+            parse_ndjson(BufReader::new(tokio::fs::File::open(path).await?), &mut stdout, workers, rules, render_config).await?
+        };
+        summary.print();
+        return Ok(());
+    }
+    if arg.is_present("file") || arg.is_present("dir") {
+        return main_files(arg, &rules);
+    }
+    let url = arg.value_of("url").unwrap().to_string();
+    let result = if arg.is_present("stdin") {
         let mut stdin = std::io::stdin();
-        parse_html(url, &mut stdin)?
+        parse_html_with_rules(url.clone(), &mut stdin, None, &rules).map_err(Error::from)
     } else {
-        let html = render(&url, api).await?;
-        parse_html(url, &mut html.as_bytes())?
+        match render(&url, api, timeout).await {
+            Ok(html) => parse_html_with_rules(url.clone(), &mut html.as_bytes(), None, &rules).map_err(Error::from),
+            Err(err) => Err(err),
+        }
     };
-    println!("url: {}", feature.url);
-    println!("title: {}", feature.title);
-    println!("image: {}", feature.image);
-    println!("content: {}", feature.content);
-    println!("feature: {}", feature.feature);
+    print_result(url, None, result, arg.value_of("output").unwrap_or("text"))
+}
+
+/// A `Feature` with what went wrong, as printed by the json outputs. A failed
+/// parse prints an empty feature for the url with the error set.
+#[derive(Serialize)]
+struct ParseOutput<'a> {
+    /// The input file, for `--file` and `--dir`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(flatten)]
+    feature: &'a Feature,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorDetail>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail {
+    kind: String,
+    message: String,
+}
+
+/// Prints a text line, keeping multi-line values on one line.
+fn print_line(key: &str, value: &str) {
+    println!("{}: {}", key, value.replace('\\', "\\\\").replace('\n', "\\n"));
+}
+
+fn print_text(feature: &Feature) {
+    print_line("url", &feature.url);
+    print_line("title", &feature.title);
+    print_line("site_name", &feature.site_name);
+    print_line("image", &feature.image);
+    for image in &feature.images {
+        print_line("images", &format!("{} {}x{} {}", image.src, image.width, image.height, image.caption));
+    }
+    print_line("description", &feature.description);
+    print_line("canonical_url", &feature.canonical_url);
+    print_line("content", &feature.content);
+    print_line("feature", &feature.feature);
+    print_line("authors", &feature.authors.join(", "));
+    print_line("published_time", &feature.published_time);
+    print_line("modified_time", &feature.modified_time);
+    print_line("section", &feature.section);
+    print_line("keywords", &feature.keywords.join(", "));
+    print_line("language", &feature.language);
+    print_line("publisher", &feature.publisher);
+    print_line("site_rule", &feature.site_rule);
+}
+
+/// Prints a parse result in the `--output` format. Json outputs print failures
+/// too; every format still ends with the error so the exit status reflects it.
+pub(crate) fn print_result(url: String, file: Option<&str>, result: Result<Feature, Error>, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if output == "json" || output == "json-pretty" {
+        let (feature, error) = match &result {
+            Ok(feature) => (feature.clone(), None),
+            Err(err) => (Feature { url, ..Feature::default() }, Some(ErrorDetail { kind: err.kind().to_string(), message: err.to_string() })),
+        };
+        let output_value = ParseOutput { file, feature: &feature, error };
+        if output == "json" {
+            println!("{}", serde_json::to_string(&output_value)?);
+        } else {
+            println!("{}", serde_json::to_string_pretty(&output_value)?);
+        }
+        return result.map(|_| ()).map_err(|err| err.into());
+    }
+    let feature = result?;
+    if let Some(file) = file {
+        println!("==> {} <==", file);
+    }
+    match output {
+        "markdown" => print!("{}", feature.to_markdown()),
+        "html" => print!("{}", feature.to_html()),
+        _ => print_text(&feature),
+    }
     Ok(())
 }
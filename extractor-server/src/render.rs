@@ -1,13 +1,18 @@
+use std::time::Duration;
+
 use reqwest::Client;
 use serde_json::Value;
 use serde_json::Map;
 
-pub async fn render(url: &str, api: &str) -> Result<String, Box<dyn std::error::Error>> {
+use crate::error::Error;
+
+pub async fn render(url: &str, api: &str, timeout: Duration) -> Result<String, Error> {
     let mut body = Map::<String, Value>::new();
     body.insert("url".into(), Value::String(url.into()));
     body.insert("task_mask".into(), Value::Number(4.into()));
-    let data = serde_json::to_string(&Value::Object(body))?;
+    let data = serde_json::to_string(&Value::Object(body)).map_err(|e| Error::Internal(e.to_string()))?;
     let resp = Client::builder()
+        .timeout(timeout)
         .build()?
         .post(api)
         .header("Content-Type", "application/json")
@@ -17,10 +22,10 @@ pub async fn render(url: &str, api: &str) -> Result<String, Box<dyn std::error::
         .await?;
 
     let html = resp.get("render_html")
-        .ok_or("not found render_html")?.as_str()
-        .ok_or("not found render_html")?.to_string();
-    if html.is_empty() {
-        Err("html is empty".into())
+        .and_then(|html| html.as_str())
+        .ok_or_else(|| Error::Render("not found render_html".to_string()))?.to_string();
+    if html.trim().is_empty() {
+        Err(Error::Extract(news_extractor::Error::EmptyHtml))
     } else {
         Ok(html)
     }
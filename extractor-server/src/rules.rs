@@ -0,0 +1,245 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use news_extractor::{RuleError, SiteRules};
+use serde::{Serialize, Deserialize};
+
+
+/// A loaded set of site rules and the version it was loaded as.
+pub struct ActiveRules {
+    pub rules: SiteRules,
+    /// Hash of the rule files' names and contents; empty without a rules dir.
+    pub version: String,
+}
+
+/// What `/admin/rules` reports.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RulesStatus {
+    pub version: String,
+    pub rules: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Site rules loaded from a directory, swapped atomically on reload. A reload
+/// with an invalid rule file keeps the rules in use and records the errors.
+pub struct RuleStore {
+    dir: Option<PathBuf>,
+    active: RwLock<Arc<ActiveRules>>,
+    errors: RwLock<Vec<String>>,
+}
+
+/// 64-bit FNV-1a, a hash that stays the same across builds so a rule version
+/// is comparable between server releases.
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Fnv64 {
+        Fnv64(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Cheap fingerprint of the rule files, to notice changes without reading them.
+/// Stats the files on the blocking pool.
+async fn fingerprint(dir: PathBuf) -> u64 {
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = DefaultHasher::new();
+        for path in SiteRules::files(&dir).unwrap_or_default() {
+            path.hash(&mut hasher);
+            if let Ok(meta) = fs::metadata(&path) {
+                meta.len().hash(&mut hasher);
+                meta.modified().unwrap_or(SystemTime::UNIX_EPOCH).hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }).await.unwrap_or_default()
+}
+
+/// Loads every rule file of `dir`, reporting the errors of all invalid files.
+/// Each file is read once, so the version always matches the rules loaded.
+fn load_dir(dir: &Path) -> Result<ActiveRules, Vec<String>> {
+    let paths = SiteRules::files(dir).map_err(|err| vec![err.to_string()])?;
+    let mut rules = SiteRules::default();
+    let mut errors = vec![];
+    let mut hasher = Fnv64::new();
+    for path in paths {
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                errors.push(RuleError::Io { path: path.display().to_string(), message: err.to_string() }.to_string());
+                continue;
+            },
+        };
+        hasher.write(path.file_name().unwrap_or_default().to_string_lossy().as_bytes());
+        hasher.write(&[0]);
+        hasher.write(&bytes);
+        let loaded = String::from_utf8(bytes)
+            .map_err(|_| RuleError::Parse { path: path.display().to_string(), message: "not utf-8".to_string() })
+            .and_then(|s| SiteRules::parse_file(&path, &s));
+        match loaded {
+            Ok(loaded) => rules.extend(loaded),
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ActiveRules { rules, version: format!("{:016x}", hasher.0) })
+}
+
+impl RuleStore {
+    /// A store for `dir`, loaded once. Errors are logged and leave it empty so
+    /// the server still starts.
+    pub fn new(dir: Option<PathBuf>) -> RuleStore {
+        let store = RuleStore {
+            dir,
+            active: RwLock::new(Arc::new(ActiveRules { rules: SiteRules::default(), version: String::new() })),
+            errors: RwLock::new(vec![]),
+        };
+        let _ = store.load();
+        store
+    }
+
+    /// The rules in use.
+    pub fn current(&self) -> Arc<ActiveRules> {
+        self.active.read().unwrap().clone()
+    }
+
+    pub fn status(&self) -> RulesStatus {
+        let active = self.current();
+        RulesStatus {
+            version: active.version.clone(),
+            rules: active.rules.iter().map(|rule| rule.name().to_string()).collect(),
+            errors: self.errors.read().unwrap().clone(),
+        }
+    }
+
+    /// Reloads the rules directory on the blocking pool and returns the new
+    /// version. On errors the previous rules stay active.
+    pub async fn reload(self: &Arc<Self>) -> Result<String, Vec<String>> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || store.load()).await
+            .unwrap_or_else(|e| Err(vec![format!("reload task failed: {}", e)]))
+    }
+
+    fn load(&self) -> Result<String, Vec<String>> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(String::new()),
+        };
+        match load_dir(dir) {
+            Ok(active) => {
+                let version = active.version.clone();
+                if version != self.current().version {
+                    tracing::info!("loaded {} site rules from {}, version {}", active.rules.len(), dir.display(), version);
+                }
+                *self.active.write().unwrap() = Arc::new(active);
+                self.errors.write().unwrap().clear();
+                Ok(version)
+            },
+            Err(errors) => {
+                for err in &errors {
+                    tracing::error!("site rules not reloaded: {}", err);
+                }
+                *self.errors.write().unwrap() = errors.clone();
+                Err(errors)
+            },
+        }
+    }
+
+    /// Reloads the rules whenever the files of the directory change.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => return,
+        };
+        let mut last = fingerprint(dir.clone()).await;
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let current = fingerprint(dir.clone()).await;
+            if current != last {
+                last = current;
+                let _ = self.reload().await;
+            }
+        }
+    }
+
+    /// Reloads the rules on SIGHUP.
+    #[cfg(unix)]
+    pub async fn reload_on_hangup(self: Arc<Self>) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                tracing::error!("listen for SIGHUP failed: {}", err);
+                return;
+            },
+        };
+        while hangup.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading site rules");
+            let _ = self.reload().await;
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn reload_on_hangup(self: Arc<Self>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+
+    use super::{Fnv64, RuleStore};
+
+    fn rule(name: &str) -> String {
+        format!("[[rules]]\nname = \"{}\"\ndomains = [\"{}.com\"]\ncontent = [\"article\"]\n", name, name)
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("extractor-rules-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.toml"), rule("a")).unwrap();
+        let store = Arc::new(RuleStore::new(Some(dir.clone())));
+        let status = store.status();
+        assert_eq!(status.rules, vec!["a"]);
+        assert!(status.errors.is_empty());
+        assert_eq!(status.version.len(), 16);
+
+        fs::write(dir.join("b.toml"), "[[rules]]\nname = \"b\"\ncontent = [\"div[\"]\n").unwrap();
+        assert!(store.reload().await.is_err());
+        let failed = store.status();
+        assert_eq!((failed.version.as_str(), failed.rules.as_slice()), (status.version.as_str(), status.rules.as_slice()));
+        assert_eq!(failed.errors.len(), 1);
+
+        fs::write(dir.join("b.toml"), rule("b")).unwrap();
+        let version = store.reload().await.unwrap();
+        let reloaded = store.status();
+        assert_ne!(version, status.version);
+        assert_eq!(reloaded.version, version);
+        assert_eq!(reloaded.rules, vec!["a", "b"]);
+        assert!(reloaded.errors.is_empty());
+        assert_eq!(store.reload().await.unwrap(), version);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        let mut hasher = Fnv64::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.0, 0xaf63dc4c8601ec8c);
+    }
+}
@@ -1,43 +1,88 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{ routing::{get, post}, response::IntoResponse, Json, Router, Extension };
+use axum::extract::rejection::JsonRejection;
+use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::AUTHORIZATION;
 use clap::ArgMatches;
 use news_extractor::Feature;
-use news_extractor::parse_html;
+use news_extractor::parse_html_with_rules;
 use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 use serde::{Serialize, Deserialize};
 
+use crate::error::Error;
 use crate::render::render;
+use crate::rules::{ActiveRules, RuleStore, RulesStatus};
 
+/// The JSON envelope of every `/parse` response.
+///
+/// `code` is 0 on success and otherwise equals the HTTP status of the
+/// response, with `error` describing the failure. `rule_version` is the
+/// version of the site rules the request was served with.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Response<R> {
     code: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     result: Option<R>,
     msg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorDetail>,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    rule_version: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ErrorDetail {
+    kind: String,
+    message: String,
 }
 
 impl <R> Response<R> {
-    fn new<E>(result: Result<R, E>) -> Response<R>
-        where E: ToString {
+    fn new(result: Result<R, Error>) -> Response<R> {
         match result {
             Ok(result) => {
                 Response {
                     code: 0,
                     result: Some(result),
                     msg: "success".to_string(),
+                    error: None,
+                    rule_version: String::new(),
                 }
             },
             Err(err) => {
                 Response {
-                    code: 0,
+                    code: err.status_code().as_u16() as i32,
                     result: None,
                     msg: err.to_string(),
+                    error: Some(ErrorDetail {
+                        kind: err.kind().to_string(),
+                        message: err.to_string(),
+                    }),
+                    rule_version: String::new(),
                 }
             },
         }
     }
+
+    fn with_rule_version(mut self, version: &str) -> Response<R> {
+        self.rule_version = version.to_string();
+        self
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.code as u16).unwrap_or(StatusCode::OK)
+    }
+}
+
+/// Most requests accepted by one `/parse/batch` call.
+const MAX_BATCH_SIZE: usize = 1000;
+
+struct ServerConfig {
+    render_timeout: Duration,
 }
 
 #[derive(Deserialize)]
@@ -47,28 +92,120 @@ struct Request {
     render_html: Option<String>,
 }
 
-async fn parse_handle(request: Request) -> Result<Feature, String> {
+async fn parse_handle(request: Request, config: &ServerConfig, rules: Arc<ActiveRules>) -> Result<Feature, Error> {
     if request.url.is_empty() {
-        return Err("not exists url".into());
+        return Err(Error::BadRequest("not exists url".into()));
     }
     let render_html = 
     if request.api.is_none() {
-        request.render_html.ok_or("not exists render_html".to_string())?
+        request.render_html.ok_or_else(|| Error::BadRequest("not exists render_html".to_string()))?
     } else {
-        render(&request.url, &request.api.unwrap()).await.map_err(|e|e.to_string())?
+        render(&request.url, &request.api.unwrap(), config.render_timeout).await?
     };
     tokio::task::spawn_blocking(move || {
-        parse_html(request.url.clone(), &mut render_html.as_bytes()).map_err(|e| e.into())
-    }).await.unwrap()
+        parse_html_with_rules(request.url.clone(), &mut render_html.as_bytes(), None, &rules.rules).map_err(Error::from)
+    }).await.map_err(|e| Error::Internal(format!("parse task failed: {}", e)))?
 }
 
 async fn hello_world() -> &'static str {
     return "hello world";
 }
 
-async fn parse(Extension(sem): Extension<Arc<Semaphore>>, Json(request): Json<Request>) -> impl IntoResponse {
-    let _sem = sem.acquire().await.unwrap();
-    Json(Response::new(parse_handle(request).await))
+async fn parse(Extension(sem): Extension<Arc<Semaphore>>, Extension(config): Extension<Arc<ServerConfig>>,
+    Extension(store): Extension<Arc<RuleStore>>, request: Result<Json<Request>, JsonRejection>) -> impl IntoResponse {
+    let rules = store.current();
+    let version = rules.version.clone();
+    let response = match request {
+        Ok(Json(request)) => {
+            let _sem = sem.acquire().await.unwrap();
+            Response::new(parse_handle(request, &config, rules).await)
+        },
+        Err(err) => Response::new(Err(Error::BadRequest(err.to_string()))),
+    };
+    let response = response.with_rule_version(&version);
+    (response.status_code(), Json(response))
+}
+
+/// Batch tasks, aborted when dropped so a batch whose client went away does
+/// not keep rendering and holding the semaphore.
+struct BatchTasks(Vec<JoinHandle<Result<Feature, Error>>>);
+
+impl Drop for BatchTasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// Parses each request of the batch concurrently, as many at a time as `sem`
+/// allows, and gives one result per request, in order.
+async fn parse_requests(requests: Vec<Request>, sem: Arc<Semaphore>, config: Arc<ServerConfig>, rules: Arc<ActiveRules>)
+    -> Result<Vec<Result<Feature, Error>>, Error> {
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(Error::BadRequest(format!("batch of {} requests exceeds {}", requests.len(), MAX_BATCH_SIZE)));
+    }
+    let mut tasks = BatchTasks(requests.into_iter().map(|request| {
+        let (sem, config, rules) = (sem.clone(), config.clone(), rules.clone());
+        tokio::spawn(async move {
+            let _sem = sem.acquire_owned().await.unwrap();
+            parse_handle(request, &config, rules).await
+        })
+    }).collect());
+    let mut results = Vec::with_capacity(tasks.0.len());
+    for task in tasks.0.iter_mut() {
+        results.push(task.await.unwrap_or_else(|e| Err(Error::Internal(format!("parse task failed: {}", e)))));
+    }
+    Ok(results)
+}
+
+async fn parse_batch(Extension(sem): Extension<Arc<Semaphore>>, Extension(config): Extension<Arc<ServerConfig>>,
+    Extension(store): Extension<Arc<RuleStore>>, requests: Result<Json<Vec<Request>>, JsonRejection>) -> impl IntoResponse {
+    let rules = store.current();
+    let version = rules.version.clone();
+    let result = match requests {
+        Ok(Json(requests)) => parse_requests(requests, sem, config, rules).await.map(|results| {
+            results.into_iter().map(|result| Response::new(result).with_rule_version(&version)).collect::<Vec<_>>()
+        }),
+        Err(err) => Err(Error::BadRequest(err.to_string())),
+    };
+    let response = Response::new(result).with_rule_version(&version);
+    (response.status_code(), Json(response))
+}
+
+/// The token the `/admin` endpoints require as `Authorization: Bearer <token>`.
+struct AdminToken(String);
+
+impl AdminToken {
+    fn check(&self, headers: &HeaderMap) -> Result<(), Error> {
+        let given = headers.get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match given {
+            Some(given) if given == self.0 => Ok(()),
+            _ => Err(Error::Unauthorized("missing or wrong admin token".to_string())),
+        }
+    }
+}
+
+async fn rules_status(Extension(store): Extension<Arc<RuleStore>>, Extension(token): Extension<Arc<AdminToken>>,
+    headers: HeaderMap) -> impl IntoResponse {
+    let result = token.check(&headers).map(|_| store.status());
+    let response = Response::new(result).with_rule_version(&store.current().version);
+    (response.status_code(), Json(response))
+}
+
+/// Reloads the site rules; invalid rule files are reported with a 400 and
+/// leave the previous rules active.
+async fn reload_rules(Extension(store): Extension<Arc<RuleStore>>, Extension(token): Extension<Arc<AdminToken>>,
+    headers: HeaderMap) -> impl IntoResponse {
+    let result = match token.check(&headers) {
+        Ok(()) => store.reload().await.map(|_| store.status())
+            .map_err(|errors| Error::BadRequest(format!("invalid site rules: {}", errors.join("; ")))),
+        Err(err) => Err(err),
+    };
+    let response: Response<RulesStatus> = Response::new(result).with_rule_version(&store.current().version);
+    (response.status_code(), Json(response))
 }
 
 pub async fn main_server(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
@@ -77,6 +214,9 @@ pub async fn main_server(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Err
     assert!(concurrency <= 64);
 
     let sem = Arc::new(Semaphore::new(concurrency as usize));
+    let config = Arc::new(ServerConfig {
+        render_timeout: Duration::from_secs(arg.value_of("render-timeout").unwrap().parse::<u64>()?),
+    });
 
     let host = arg.value_of("host").unwrap();
     let port = arg.value_of("port").unwrap();
@@ -84,9 +224,32 @@ pub async fn main_server(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Err
 
     tracing_subscriber::fmt::init();
 
-    let app = Router::new()
+    let rules_dir = arg.value_of("rules-dir").map(PathBuf::from);
+    let store = Arc::new(RuleStore::new(rules_dir.clone()));
+    if rules_dir.is_some() {
+        let poll = Duration::from_secs(arg.value_of("rules-poll").unwrap().parse::<u64>()?.max(1));
+        tokio::spawn(store.clone().watch(poll));
+        tokio::spawn(store.clone().reload_on_hangup());
+    }
+
+    let mut app = Router::new()
         .route("/", get(hello_world))
-        .route("/parse", post(parse)).layer(Extension(sem));
+        .route("/parse", post(parse))
+        .route("/parse/batch", post(parse_batch));
+    // The admin endpoints are only served with a token to guard them.
+    let admin_token = arg.value_of("admin-token").map(str::to_string)
+        .or_else(|| std::env::var("EXTRACTOR_ADMIN_TOKEN").ok())
+        .filter(|token| !token.trim().is_empty());
+    if let Some(token) = admin_token {
+        app = app
+            .route("/admin/rules", get(rules_status))
+            .route("/admin/rules/reload", post(reload_rules))
+            .layer(Extension(Arc::new(AdminToken(token))));
+    }
+    let app = app
+        .layer(Extension(sem))
+        .layer(Extension(config))
+        .layer(Extension(store));
 
     tracing::info!("listening on {}", addr);
     axum::Server::bind(&addr)
@@ -96,3 +259,92 @@ pub async fn main_server(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Err
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use news_extractor::SiteRules;
+    use tokio::sync::Semaphore;
+
+    use axum::{routing::post, Json, Router};
+    use serde_json::json;
+
+    use axum::http::{HeaderMap, HeaderValue};
+    use axum::http::header::AUTHORIZATION;
+
+    use super::{parse_handle, parse_requests, ActiveRules, AdminToken, Request, ServerConfig, MAX_BATCH_SIZE};
+
+    fn request(i: usize) -> Request {
+        let html = format!("<html><head><title>Page {}</title></head><body><p>Text</p></body></html>", i);
+        Request { url: format!("https://example.com/{}", i), api: None, render_html: Some(html) }
+    }
+
+    fn setup() -> (Arc<Semaphore>, Arc<ServerConfig>, Arc<ActiveRules>) {
+        let config = ServerConfig { render_timeout: Duration::from_secs(1) };
+        let rules = ActiveRules { rules: SiteRules::default(), version: String::new() };
+        (Arc::new(Semaphore::new(2)), Arc::new(config), Arc::new(rules))
+    }
+
+    #[tokio::test]
+    async fn test_batch_in_order_with_failures() {
+        let (sem, config, rules) = setup();
+        let mut requests = (0..10).map(request).collect::<Vec<_>>();
+        requests[3].url = String::new();
+        let results = parse_requests(requests, sem, config, rules).await.unwrap();
+        assert_eq!(results.len(), 10);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(feature) => assert_eq!(feature.title, format!("Page {}", i)),
+                Err(err) => assert_eq!((i, err.kind()), (3, "bad_request")),
+            }
+        }
+
+        let (sem, config, rules) = setup();
+        let requests = (0..MAX_BATCH_SIZE + 1).map(request).collect::<Vec<_>>();
+        assert!(matches!(parse_requests(requests, sem, config, rules).await, Err(err) if err.kind() == "bad_request"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_aborted_on_drop() {
+        let (sem, config, rules) = setup();
+        let _held = sem.clone().acquire_many_owned(2).await.unwrap();
+        let requests = (0..5).map(request).collect::<Vec<_>>();
+        let batch = parse_requests(requests, sem, config, rules.clone());
+        assert!(tokio::time::timeout(Duration::from_millis(50), batch).await.is_err());
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(Arc::strong_count(&rules), 1);
+    }
+
+    #[tokio::test]
+    async fn test_empty_html_kind() {
+        let render_server = Router::new().route("/render", post(|| async { Json(json!({"render_html": " "})) }));
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(render_server.into_make_service());
+        let api = format!("http://{}/render", server.local_addr());
+        tokio::spawn(server);
+
+        let (_, config, rules) = setup();
+        let rendered = Request { url: "https://example.com/".to_string(), api: Some(api), render_html: None };
+        let inline = Request { url: "https://example.com/".to_string(), api: None, render_html: Some(String::new()) };
+        for request in [rendered, inline] {
+            let err = parse_handle(request, &config, rules.clone()).await.unwrap_err();
+            assert_eq!((err.kind(), err.status_code().as_u16()), ("empty_html", 422));
+        }
+    }
+
+    #[test]
+    fn test_admin_token() {
+        let token = AdminToken("secret".to_string());
+        let mut headers = HeaderMap::new();
+        assert_eq!(token.check(&headers).unwrap_err().status_code().as_u16(), 401);
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        assert!(token.check(&headers).is_err());
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("secret"));
+        assert!(token.check(&headers).is_err());
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        assert!(token.check(&headers).is_ok());
+    }
+}
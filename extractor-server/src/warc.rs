@@ -0,0 +1,69 @@
+use std::io::Write;
+
+use clap::ArgMatches;
+use news_extractor::{parse_warc, Feature, SiteRules};
+use serde::Serialize;
+
+
+/// One output line: the record and either its feature or why it failed.
+#[derive(Serialize)]
+struct WarcLine {
+    record_id: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feature: Option<Feature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorDetail>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail {
+    kind: String,
+    message: String,
+}
+
+/// Extracts the html responses of a WARC archive and writes one JSON line per
+/// record to stdout, with a summary on stderr.
+pub fn main_warc(arg: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let path = arg.value_of("path").unwrap();
+    let rules = match arg.value_of("rules") {
+        Some(path) => SiteRules::load(path)?,
+        None => SiteRules::default(),
+    };
+    let records = if path == "-" {
+        parse_warc(std::io::stdin(), &rules)?
+    } else {
+        parse_warc(std::fs::File::open(path).map_err(|err| format!("open {} failed: {}", path, err))?, &rules)?
+    };
+    let (mut parsed, mut failed) = (0, 0);
+    let mut stdout = std::io::stdout().lock();
+    let mut read_error = None;
+    for record in records {
+        // A record the reader cannot frame leaves no way to find the next one.
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                read_error = Some(err);
+                break;
+            },
+        };
+        let line = match record.result {
+            Ok(feature) => {
+                parsed += 1;
+                WarcLine { record_id: record.record_id, url: record.url, feature: Some(feature), error: None }
+            },
+            Err(err) => {
+                failed += 1;
+                let error = ErrorDetail { kind: err.kind().to_string(), message: err.to_string() };
+                WarcLine { record_id: record.record_id, url: record.url, feature: None, error: Some(error) }
+            },
+        };
+        serde_json::to_writer(&mut stdout, &line)?;
+        stdout.write_all(b"\n")?;
+    }
+    eprintln!("parsed: {}, failed: {}", parsed, failed);
+    match read_error {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
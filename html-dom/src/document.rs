@@ -17,6 +17,8 @@ use crate::node::new_handle;
 pub struct Document<ND> where ND: NodeDataGetter + Default {
     pub document: Handle<ND>,
     pub errors: Vec<String>,
+    /// Name of the character encoding the document was decoded with.
+    pub encoding: String,
 }
 
 impl <ND> Document<ND> where ND: NodeDataGetter + Default {
@@ -24,6 +26,7 @@ impl <ND> Document<ND> where ND: NodeDataGetter + Default {
         Document::<ND> {
             document: new_handle(Node::new_document_node()),
             errors: vec!(),
+            encoding: "UTF-8".to_string(),
         }
     }
 }
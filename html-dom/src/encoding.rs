@@ -0,0 +1,180 @@
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+
+/// Number of leading bytes searched for a `<meta>` charset declaration.
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Extracts the charset from a `Content-Type` header value such as
+/// `text/html; charset=gbk`. A bare label like `gbk` is accepted as well.
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    let label = if content_type.contains('=') || content_type.contains(';') {
+        content_type.split(';')
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value.trim().trim_matches(|c| c == '"' || c == '\''))?
+    } else {
+        content_type.trim()
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+}
+
+fn skip_spaces(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && is_space(bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+fn starts_with_ignore_case(bytes: &[u8], i: usize, prefix: &[u8]) -> bool {
+    bytes.len() >= i + prefix.len() && bytes[i..i + prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Reads one attribute of a tag starting at `i`, returning the lowercased name,
+/// the raw value and the position after it, or `None` at the end of the tag.
+fn read_attribute(bytes: &[u8], i: usize) -> Option<(String, String, usize)> {
+    let mut i = skip_spaces(bytes, i);
+    while i < bytes.len() && bytes[i] == b'/' {
+        i = skip_spaces(bytes, i + 1);
+    }
+    if i >= bytes.len() || bytes[i] == b'>' {
+        return None;
+    }
+    let start = i;
+    while i < bytes.len() && !is_space(bytes[i]) && !matches!(bytes[i], b'=' | b'>' | b'/') {
+        i += 1;
+    }
+    let name = String::from_utf8_lossy(&bytes[start..i]).to_ascii_lowercase();
+    i = skip_spaces(bytes, i);
+    if i >= bytes.len() || bytes[i] != b'=' {
+        return Some((name, String::new(), i));
+    }
+    i = skip_spaces(bytes, i + 1);
+    let value = match bytes.get(i) {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            let end = bytes[i + 1..].iter().position(|&b| b == quote)? + i + 1;
+            let value = &bytes[i + 1..end];
+            i = end + 1;
+            value
+        },
+        _ => {
+            let start = i;
+            while i < bytes.len() && !is_space(bytes[i]) && bytes[i] != b'>' {
+                i += 1;
+            }
+            &bytes[start..i]
+        },
+    };
+    Some((name, String::from_utf8_lossy(value).to_string(), i))
+}
+
+/// A simplified version of the WHATWG "prescan a byte stream to determine its
+/// encoding" algorithm.
+fn prescan_meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let bytes = &bytes[..bytes.len().min(PRESCAN_LENGTH)];
+    let mut i = 0;
+    while i < bytes.len() {
+        if starts_with_ignore_case(bytes, i, b"<!--") {
+            i = bytes[i + 4..].windows(3).position(|w| w == b"-->").map(|end| i + 4 + end + 3)?;
+        } else if starts_with_ignore_case(bytes, i, b"<meta")
+            && bytes.get(i + 5).is_some_and(|&b| is_space(b) || b == b'/') {
+            i += 5;
+            let mut charset = None;
+            let mut content = None;
+            let mut http_equiv = false;
+            while let Some((name, value, next)) = read_attribute(bytes, i) {
+                i = next;
+                match name.as_str() {
+                    "charset" => charset = charset.or_else(|| Encoding::for_label(value.trim().as_bytes())),
+                    "content" => content = content.or(Some(value)),
+                    "http-equiv" => http_equiv = http_equiv || value.eq_ignore_ascii_case("content-type"),
+                    _ => {},
+                }
+            }
+            let encoding = charset.or_else(|| {
+                content.filter(|_| http_equiv).and_then(|content| charset_from_content_type(&content))
+            });
+            if let Some(encoding) = encoding {
+                // A document that declares UTF-16 in its markup can only have been
+                // decoded as an ASCII-compatible encoding to read the declaration.
+                return Some(match encoding.name() {
+                    "UTF-16LE" | "UTF-16BE" => UTF_8,
+                    _ if encoding == X_USER_DEFINED => WINDOWS_1252,
+                    _ => encoding,
+                });
+            }
+        } else if bytes[i] == b'<' && bytes.get(i + 1).is_some_and(|b| b.is_ascii_alphabetic() || *b == b'/') {
+            i = bytes[i..].iter().position(|&b| b == b'>').map(|end| i + end + 1)?;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Picks the encoding of an HTML byte stream: a byte order mark wins, then the
+/// caller's `Content-Type` hint, then a `<meta>` declaration, and finally UTF-8
+/// or windows-1252 depending on whether the bytes are valid UTF-8.
+///
+/// Pages delivered by a render server are re-serialized as UTF-8 but keep their
+/// original `<meta charset>`, so a declaration is ignored when the bytes are
+/// valid UTF-8 with non-ASCII content.
+pub fn detect_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return encoding;
+    }
+    let utf8 = std::str::from_utf8(bytes).is_ok();
+    match prescan_meta_charset(bytes) {
+        Some(encoding) if !utf8 || bytes.is_ascii() => encoding,
+        _ if utf8 => UTF_8,
+        _ => WINDOWS_1252,
+    }
+}
+
+/// Decodes an HTML byte stream, returning the text and the encoding used.
+pub fn decode_html(bytes: &[u8], content_type: Option<&str>) -> (String, &'static Encoding) {
+    let encoding = detect_encoding(bytes, content_type);
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, length)) if bom_encoding == encoding => &bytes[length..],
+        _ => bytes,
+    };
+    (encoding.decode_without_bom_handling(bytes).0.into_owned(), encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_8, UTF_16LE, WINDOWS_1251, WINDOWS_1252};
+
+    use super::{detect_encoding, decode_html};
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"\xEF\xBB\xBF<html>", Some("text/html; charset=gbk")), UTF_8);
+        assert_eq!(detect_encoding(b"\xFF\xFE<\x00h\x00", None), UTF_16LE);
+        assert_eq!(detect_encoding(b"<html>", Some("text/html; charset=\"Shift_JIS\"")), SHIFT_JIS);
+        assert_eq!(detect_encoding(b"<!-- <meta charset=gbk> --><meta charset='gbk'>", None), GBK);
+        assert_eq!(detect_encoding(
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1251\">", None), WINDOWS_1251);
+        assert_eq!(detect_encoding(b"<meta charset=\"utf-16\">", None), UTF_8);
+        assert_eq!(detect_encoding(b"<p>caf\xE9</p>", None), WINDOWS_1252);
+        assert_eq!(detect_encoding("<meta charset=gbk><p>中文</p>".as_bytes(), None), UTF_8);
+    }
+
+    #[test]
+    fn test_decode_html() {
+        let (html, _, _) = GBK.encode("<meta charset=gbk><title>新闻</title>");
+        let (text, encoding) = decode_html(&html, None);
+        assert_eq!(encoding, GBK);
+        assert_eq!(text, "<meta charset=gbk><title>新闻</title>");
+
+        let (text, encoding) = decode_html(b"\xEF\xBB\xBF<p>ok</p>", None);
+        assert_eq!(encoding, UTF_8);
+        assert_eq!(text, "<p>ok</p>");
+    }
+}
@@ -0,0 +1,24 @@
+use std::fmt;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Reading the document failed.
+    Io(String),
+    /// A CSS selector could not be parsed.
+    Selector(String),
+    /// An XPath expression could not be parsed or evaluated.
+    XPath(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(msg) => write!(f, "io error: {}", msg),
+            Error::Selector(msg) => write!(f, "selector error: {}", msg),
+            Error::XPath(msg) => write!(f, "xpath error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
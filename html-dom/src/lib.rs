@@ -1,23 +1,38 @@
 mod node;
 mod iterator;
 mod document;
+mod error;
+mod encoding;
+mod markdown;
 mod parser;
+mod selector;
+mod xpath;
 
 pub use parser::parse_document;
 pub use parser::custom_parse_document;
+pub use parser::parse_document_with_charset;
+pub use parser::custom_parse_document_with_charset;
+pub use encoding::detect_encoding;
+pub use markdown::to_markdown;
 pub use node::NodeData;
 pub use node::NodeDataGetter;
 pub use node::Handle;
 pub use node::Node;
+pub use node::new_handle;
 pub use document::Document;
+pub use error::Error;
 pub use node::is_same_handle;
+pub use selector::Selector;
+pub use xpath::XPath;
+pub use xpath::XPathNode;
+pub use xpath::XPathValue;
 
 #[cfg(test)]
 mod tests {
 
 use html5ever::tree_builder::TreeSink;
 
-use crate::{parse_document, custom_parse_document, node::{NodeData, NodeDataGetter}};
+use crate::{parse_document, custom_parse_document, Node, node::{DefaultNodeData, NodeData, NodeDataGetter}};
 
 const HTML: &str = r#"<!DOCTYPE html><html><head>
 <style>
@@ -89,4 +104,32 @@ fn test_custom_parser() {
     let outer_html = doc.get_document().borrow().outer_html();
     assert_eq!(outer_html.len(), HTML.len());
 }
-}
\ No newline at end of file
+
+#[test]
+fn test_missing_body() {
+    let doc = match parse_document(&mut "<html><head><title>t</title></head><frameset></frameset></html>".as_bytes()) {
+        Ok(doc) => doc,
+        Err(err) => panic!("{}", err)
+    };
+    let document = doc.document.borrow();
+    assert!(document.try_root().is_some());
+    assert!(document.try_head().is_some());
+    assert!(document.try_body().is_none());
+    assert_eq!(document.title(), Some("t".to_string()));
+
+    let detached = Node::<DefaultNodeData>::new_text_node("text".to_string());
+    assert!(detached.try_document_node().is_none());
+    assert!(detached.try_root().is_none());
+}
+
+#[test]
+fn test_shallow_clone() {
+    let doc = parse_document(&mut r#"<html><body><a href="/a" class="x">link</a></body></html>"#.as_bytes()).unwrap();
+    let link = doc.document.borrow().select_first("a").unwrap().unwrap();
+    let mut copy = link.borrow().shallow_clone();
+    copy.set_attr("href", "https://example.com/a");
+    copy.set_attr("rel", "nofollow");
+    assert_eq!(copy.outer_html(), r#"<a href="https://example.com/a" class="x" rel="nofollow"></a>"#);
+    assert_eq!(link.borrow().attr("href"), Some("/a".to_string()));
+}
+}
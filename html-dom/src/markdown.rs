@@ -0,0 +1,354 @@
+use crate::node::{Handle, Node, NodeDataGetter};
+
+
+/// Characters with a meaning in inline Markdown. `<` and `>` are escaped so
+/// that decoded text cannot turn into raw html.
+const ESCAPED_CHARS: [char; 8] = ['\\', '*', '_', '`', '[', ']', '<', '>'];
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if ESCAPED_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes the start of a line that would otherwise begin a heading, list,
+/// quote or thematic break. Quote markers are already escaped by `escape`.
+fn escape_line_start(line: &str) -> String {
+    if line.starts_with(['#', '-', '+', '=']) {
+        return format!("\\{}", line);
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &line[digits..];
+    if digits > 0 && rest.starts_with(['.', ')']) && (rest.len() == 1 || rest[1..].starts_with(char::is_whitespace)) {
+        return format!("{}\\{}", &line[..digits], rest);
+    }
+    line.to_string()
+}
+
+/// Percent-encodes the characters that would end a Markdown link destination.
+fn escape_url(url: &str) -> String {
+    url.trim().replace(' ', "%20").replace('(', "%28").replace(')', "%29").replace('<', "%3C").replace('>', "%3E")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                collapsed.push(' ');
+            }
+            space = false;
+            collapsed.push(c);
+        }
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// Trims the lines of inline Markdown and turns the `\n` left by `<br>` into
+/// hard line breaks.
+fn finish_inline(text: &str) -> String {
+    let lines = text.split('\n').map(|line| {
+        let mut line = line.trim().to_string();
+        while line.contains("  ") {
+            line = line.replace("  ", " ");
+        }
+        escape_line_start(&line)
+    }).collect::<Vec<_>>();
+    let first = lines.iter().position(|line| !line.is_empty());
+    let last = lines.iter().rposition(|line| !line.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last].join("  \n"),
+        _ => String::new(),
+    }
+}
+
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.lines().enumerate().map(|(i, line)| {
+        let prefix = if i == 0 { first } else { rest };
+        if line.is_empty() { prefix.trim_end().to_string() } else { format!("{}{}", prefix, line) }
+    }).collect::<Vec<_>>().join("\n")
+}
+
+fn is_block<ND>(node: &Node<ND>) -> bool where ND: NodeDataGetter + Default {
+    node.is_element_node() && !node.is_inline_tag()
+}
+
+fn element_children<ND>(node: &Node<ND>, tags: &[&str]) -> Vec<Handle<ND>> where ND: NodeDataGetter + Default {
+    node.children().filter(|child| {
+        let child = child.borrow();
+        child.is_element_node() && tags.contains(&child.tag_name().as_str())
+    }).collect()
+}
+
+/// Renders the content of an element as inline Markdown.
+fn inline<ND>(node: &Node<ND>) -> String where ND: NodeDataGetter + Default {
+    let mut text = String::new();
+    for child in node.children() {
+        inline_node(&child.borrow(), &mut text);
+    }
+    finish_inline(&text)
+}
+
+fn inline_node<ND>(node: &Node<ND>, out: &mut String) where ND: NodeDataGetter + Default {
+    if node.is_text_node() {
+        out.push_str(&escape(&collapse_whitespace(&node.text())));
+        return;
+    }
+    if !node.is_element_node() || node.is_none_tag() {
+        return;
+    }
+    let wrap = |out: &mut String, marker: &str| {
+        let content = inline(node);
+        if !content.is_empty() {
+            out.push_str(&format!("{}{}{}", marker, content, marker));
+        }
+    };
+    match node.tag_name().as_str() {
+        "br" => out.push('\n'),
+        "em" | "i" | "cite" => wrap(out, "*"),
+        "strong" | "b" => wrap(out, "**"),
+        "del" | "s" | "strike" => wrap(out, "~~"),
+        "code" | "kbd" | "samp" => {
+            let code = collapse_whitespace(&node.descendants_text());
+            let code = code.trim();
+            if !code.is_empty() {
+                if code.contains('`') {
+                    out.push_str(&format!("`` {} ``", code));
+                } else {
+                    out.push_str(&format!("`{}`", code));
+                }
+            }
+        },
+        "a" => {
+            let content = inline(node);
+            match node.attr("href").filter(|href| !href.trim().is_empty()) {
+                Some(href) if !content.is_empty() => out.push_str(&format!("[{}]({})", content, escape_url(&href))),
+                _ => out.push_str(&content),
+            }
+        },
+        "img" => {
+            if let Some(src) = node.attr("src").filter(|src| !src.trim().is_empty()) {
+                let alt = escape(&collapse_whitespace(&node.attr("alt").unwrap_or_default()));
+                out.push_str(&format!("![{}]({})", alt.trim(), escape_url(&src)));
+            }
+        },
+        _ if is_block(node) => {
+            // A block nested in inline content, e.g. a <div> inside a <span>.
+            out.push('\n');
+            node.children().for_each(|child| inline_node(&child.borrow(), out));
+            out.push('\n');
+        },
+        _ => node.children().for_each(|child| inline_node(&child.borrow(), out)),
+    }
+}
+
+/// Collects Markdown blocks; inline content between blocks becomes a paragraph.
+struct MarkdownWriter {
+    blocks: Vec<String>,
+    loose: String,
+}
+
+impl MarkdownWriter {
+    fn flush(&mut self) {
+        let paragraph = finish_inline(&std::mem::take(&mut self.loose));
+        if !paragraph.is_empty() {
+            self.blocks.push(paragraph);
+        }
+    }
+
+    fn push(&mut self, block: String) {
+        self.flush();
+        if !block.trim().is_empty() {
+            self.blocks.push(block);
+        }
+    }
+
+    fn children<ND>(&mut self, node: &Node<ND>) where ND: NodeDataGetter + Default {
+        for child in node.children() {
+            self.node(&child.borrow());
+        }
+    }
+
+    fn node<ND>(&mut self, node: &Node<ND>) where ND: NodeDataGetter + Default {
+        if !is_block(node) {
+            if node.is_text_node() || node.is_element_node() {
+                inline_node(node, &mut self.loose);
+            }
+            return;
+        }
+        let tag = node.tag_name();
+        match tag.as_str() {
+            _ if node.is_none_tag() => {},
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = tag[1..].parse::<usize>().unwrap_or(1);
+                let text = inline(node).replace("  \n", " ");
+                if !text.is_empty() {
+                    self.push(format!("{} {}", "#".repeat(level), text));
+                }
+            },
+            "p" => self.push(inline(node)),
+            "hr" => self.push("---".to_string()),
+            "pre" => {
+                let language = element_children(node, &["code"]).first()
+                    .and_then(|code| code.borrow().attr("class"))
+                    .and_then(|class| class.split_whitespace()
+                        .find_map(|name| name.strip_prefix("language-").map(str::to_string)))
+                    .unwrap_or_default();
+                let code = node.descendants_text();
+                let code = code.trim_matches('\n');
+                let fence = if code.contains("```") { "~~~" } else { "```" };
+                self.push(format!("{}{}\n{}\n{}", fence, language, code, fence));
+            },
+            "blockquote" => self.push(prefix_lines(&to_markdown(node), "> ", "> ")),
+            "ul" | "ol" => self.push(list(node, tag == "ol")),
+            "table" => self.push(table(node)),
+            _ => {
+                self.flush();
+                self.children(node);
+                self.flush();
+            },
+        }
+    }
+}
+
+fn list<ND>(node: &Node<ND>, ordered: bool) -> String where ND: NodeDataGetter + Default {
+    let start = node.attr("start").and_then(|start| start.trim().parse::<usize>().ok()).unwrap_or(1);
+    element_children(node, &["li"]).iter().enumerate()
+        .map(|(i, item)| {
+            let marker = if ordered { format!("{}. ", start + i) } else { "- ".to_string() };
+            let content = to_markdown(&item.borrow());
+            let indent = " ".repeat(marker.len());
+            prefix_lines(&content, &marker, &indent)
+        })
+        .filter(|item| !item.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table<ND>(node: &Node<ND>) -> String where ND: NodeDataGetter + Default {
+    let mut rows = element_children(node, &["tr"]);
+    for section in element_children(node, &["thead", "tbody", "tfoot"]) {
+        rows.extend(element_children(&section.borrow(), &["tr"]));
+    }
+    let rows = rows.iter()
+        .map(|row| element_children(&row.borrow(), &["th", "td"]).iter()
+            .map(|cell| inline(&cell.borrow()).replace("  \n", " ").replace('|', "\\|"))
+            .collect::<Vec<_>>())
+        .filter(|cells| !cells.is_empty())
+        .collect::<Vec<_>>();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    if columns == 0 {
+        return String::new();
+    }
+    let line = |cells: &[String]| {
+        let cells = (0..columns).map(|i| cells.get(i).map(String::as_str).unwrap_or_default()).collect::<Vec<_>>();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// Renders the content of a node as Markdown: headings, paragraphs, emphasis,
+/// links, images, lists, block quotes, code and tables. Other elements are
+/// rendered through their content; scripts and styles are left out.
+pub fn to_markdown<ND>(node: &Node<ND>) -> String where ND: NodeDataGetter + Default {
+    let mut writer = MarkdownWriter { blocks: vec![], loose: String::new() };
+    writer.children(node);
+    writer.flush();
+    writer.blocks.join("\n\n")
+}
+
+impl<ND> Node<ND> where ND: NodeDataGetter + Default {
+    pub fn markdown(&self) -> String {
+        to_markdown(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_document;
+
+    #[test]
+    fn test_markdown() {
+        let html = r#"<html><body><h1>Big  <em>news</em></h1>
+<p>Some <strong>bold</strong> and <i>italic</i> text with a <a href="https://example.com/a">link</a>,
+<code>x = 1</code> and 2*3.<script>alert(1)</script></p>
+<figure><img src="https://example.com/a.jpg" alt="A picture"></figure>
+<ul><li>One</li><li>Two<ol start="3"><li>Three</li></ol></li></ul>
+<blockquote><p>Quoted</p><p>Twice</p></blockquote>
+<pre><code class="language-rust">fn main() {
+    println!("hi");
+}</code></pre>
+<table><tr><th>Name</th><th>Age</th></tr><tr><td>Ann</td><td>30</td></tr></table>
+</body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        assert_eq!(body.borrow().markdown(), r#"# Big *news*
+
+Some **bold** and *italic* text with a [link](https://example.com/a), `x = 1` and 2\*3.
+
+![A picture](https://example.com/a.jpg)
+
+- One
+- Two
+
+  3. Three
+
+> Quoted
+>
+> Twice
+
+```rust
+fn main() {
+    println!("hi");
+}
+```
+
+| Name | Age |
+| --- | --- |
+| Ann | 30 |"#);
+    }
+
+    #[test]
+    fn test_loose_text() {
+        let doc = parse_document(&mut "<html><body><div>Loose <b>text</b><p>Para<br> break</p> tail</div></body></html>".as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        assert_eq!(body.borrow().markdown(), "Loose **text**\n\nPara  \nbreak\n\ntail");
+    }
+
+    #[test]
+    fn test_escaping() {
+        let html = r#"<html><body><p>&lt;script&gt;alert(1)&lt;/script&gt; &lt;img src=x onerror=alert(1)&gt;</p>
+<p># Not a heading</p><p>12. Not a list</p><p>- Nor this<br>+ nor this</p><p>&gt; Not a quote</p><p>2022. A year</p>
+<p><a href="https://en.wikipedia.org/wiki/Rust_(language)">Rust</a> <img src="https://example.com/a (1).png" alt="<b>"></p>
+</body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        assert_eq!(body.borrow().markdown(), r#"\<script\>alert(1)\</script\> \<img src=x onerror=alert(1)\>
+
+\# Not a heading
+
+12\. Not a list
+
+\- Nor this  
+\+ nor this
+
+\> Not a quote
+
+2022\. A year
+
+[Rust](https://en.wikipedia.org/wiki/Rust_%28language%29) ![\<b\>](https://example.com/a%20%281%29.png)"#);
+    }
+}
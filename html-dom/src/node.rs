@@ -1,8 +1,10 @@
 use std::{rc::{Rc, Weak}, cell::RefCell, collections::HashSet, ptr, ops::DerefMut};
 
-use html5ever::{QualName, Attribute, serialize::{Serialize, serialize, SerializeOpts, TraversalScope}};
+use html5ever::{ns, namespace_url, LocalName, QualName, Attribute, serialize::{Serialize, serialize, SerializeOpts, TraversalScope}};
 
 use crate::iterator::{ChildrenIterator, DescendantIterator, DescendantOrder};
+use crate::error::Error;
+use crate::selector::Selector;
 
 
 pub type Handle<ND> = Rc<RefCell<Node<ND>>>;
@@ -271,6 +273,14 @@ impl<ND> Node<ND> where ND: NodeDataGetter + Default {
         panic_is_not_text_node()
     }
 
+    pub fn set_text(&mut self, new_text: String) {
+        if let Node::Text{text, ..} = self {
+            *text = new_text;
+            return;
+        }
+        panic_is_not_text_node()
+    }
+
     pub fn comment(&self) -> String {
         if let Node::Comment{text, ..} = self {
             return text.clone();
@@ -279,13 +289,13 @@ impl<ND> Node<ND> where ND: NodeDataGetter + Default {
     }
 
     pub fn title(&self) -> Option<String> {
-        self.head().borrow().children().find(|child| 
+        self.try_head()?.borrow().children().find(|child| 
             child.borrow().is_element_node() && child.borrow().tag_name() == "title")
             .map(|node| node.borrow().children_text())
     }
 
     pub fn ld_json(&self) -> Option<String> {
-        self.head().borrow().children().find(|child| {
+        self.try_head()?.borrow().children().find(|child| {
                 child.borrow().is_element_node() && child.borrow().tag_name() == "script" 
                     && child.borrow().attr("type") == Some("application/ld+json".to_string())
             }).map(|node| node.borrow().children_text())
@@ -415,6 +425,18 @@ impl<ND> Node<ND> where ND: NodeDataGetter + Default {
             .map(|attr| attr.value.to_string())
     }
 
+    /// Sets an attribute without namespace, replacing its value if present.
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        let attrs = self.mut_attrs();
+        match attrs.iter_mut().find(|attr| attr.name.local.as_ref() == name) {
+            Some(attr) => attr.value = value.into(),
+            None => attrs.push(Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(name)),
+                value: value.into(),
+            }),
+        }
+    }
+
     pub fn add_attrs_if_missing(&mut self, attrs: Vec<Attribute>) {
         let mut_attrs = self.mut_attrs();
         let existing_names = mut_attrs.iter().map(|e| e.name.clone()).collect::<HashSet<_>>();
@@ -463,6 +485,20 @@ impl<ND> Node<ND> where ND: NodeDataGetter + Default {
         *self.mut_node_data() = Default::default();
     }
 
+    /// Copies the node without its children, parent or siblings; the custom
+    /// node data is reset to its default.
+    pub fn shallow_clone(&self) -> Self {
+        match self {
+            Node::Text { text, .. } => Node::new_text_node(text.clone()),
+            Node::Comment { text, .. } => Node::new_comment_node(text.clone()),
+            Node::Element { name, attrs, .. } => Node::new_element_node(name.clone(), attrs.clone()),
+            Node::Document { .. } => Node::new_document_node(),
+            Node::DocType { name, public_id, system_id, .. } => {
+                Node::new_doctype_node(name.clone(), public_id.clone(), system_id.clone())
+            },
+        }
+    }
+
     pub fn children(&self) -> ChildrenIterator<ND> {
         ChildrenIterator{
             node: self.first_child(),
@@ -485,35 +521,58 @@ impl<ND> Node<ND> where ND: NodeDataGetter + Default {
         DescendantIterator::new(self, DescendantOrder::PostOrder, f)
     }
 
+    pub fn select(&self, selectors: &str) -> Result<Vec<Handle<ND>>, Error> {
+        let selector = Selector::parse(selectors)?;
+        Ok(self.descendants().filter(|node| selector.matches(&node.borrow())).collect())
+    }
+
+    pub fn select_first(&self, selectors: &str) -> Result<Option<Handle<ND>>, Error> {
+        let selector = Selector::parse(selectors)?;
+        Ok(self.descendants().find(|node| selector.matches(&node.borrow())))
+    }
+
     pub fn document_node(&self) -> Handle<ND> {
-        match self {
-            Node::Document{..} => {
-                let a = self.first_child().unwrap();
-                let b = a.as_ref().borrow().parent().unwrap();
-                b
-            }
-            _ => {
-                self.parent().unwrap().as_ref().borrow().document_node()
-            }
-        }
+        self.try_document_node().expect("not exists document node")
     }
 
     pub fn root(&self) -> Handle<ND> {
-        self.document_node().as_ref().borrow().children().find(|child| child.as_ref().borrow().is_element_node()).unwrap()
+        self.try_root().expect("not exists root element")
     }
 
     pub fn body(&self) -> Handle<ND> {
-        self.root().as_ref().borrow().children().find(|child| {
-            let child = child.as_ref().borrow();
-            child.is_element_node() && child.tag_name() == "body"
-        }).unwrap()
+        self.try_body().expect("not exists body element")
     }
 
     pub fn head(&self) -> Handle<ND> {
-        self.root().as_ref().borrow().children().find(|child| {
-            let child = child.as_ref().borrow();
-            child.is_element_node() && child.tag_name() == "head"
-        }).unwrap()
+        self.try_head().expect("not exists head element")
+    }
+
+    /// The document node this node belongs to, or `None` for an empty document
+    /// or a node detached from any document.
+    pub fn try_document_node(&self) -> Option<Handle<ND>> {
+        match self {
+            Node::Document{..} => self.first_child()?.borrow().parent(),
+            _ => self.parent()?.borrow().try_document_node(),
+        }
+    }
+
+    pub fn try_root(&self) -> Option<Handle<ND>> {
+        self.try_document_node()?.borrow().children().find(|child| child.borrow().is_element_node())
+    }
+
+    pub fn try_body(&self) -> Option<Handle<ND>> {
+        self.try_root_child("body")
+    }
+
+    pub fn try_head(&self) -> Option<Handle<ND>> {
+        self.try_root_child("head")
+    }
+
+    fn try_root_child(&self, tag_name: &str) -> Option<Handle<ND>> {
+        self.try_root()?.borrow().children().find(|child| {
+            let child = child.borrow();
+            child.is_element_node() && child.tag_name() == tag_name
+        })
     }
 
     pub fn children_text(&self) -> String {
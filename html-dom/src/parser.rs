@@ -5,10 +5,19 @@ use html5ever::tendril::TendrilSink;
 use html5ever::tree_builder::TreeBuilderOpts;
 
 use crate::document::Document;
+use crate::encoding::decode_html;
+use crate::error::Error;
 use crate::node::{NodeDataGetter, DefaultNodeData};
 
 
-pub fn custom_parse_document<R, ND>(r: &mut R) -> Result<Document<ND>, String>
+pub fn custom_parse_document<R, ND>(r: &mut R) -> Result<Document<ND>, Error>
+where R: io::Read, ND: NodeDataGetter + Default {
+    custom_parse_document_with_charset(r, None)
+}
+
+/// Parses a document whose encoding is sniffed from its byte order mark, the
+/// `content_type` hint (a `Content-Type` header value) or its `<meta>` tags.
+pub fn custom_parse_document_with_charset<R, ND>(r: &mut R, content_type: Option<&str>) -> Result<Document<ND>, Error>
 where R: io::Read, ND: NodeDataGetter + Default {
     let opts = ParseOpts {
         tree_builder: TreeBuilderOpts {
@@ -18,19 +27,21 @@ where R: io::Read, ND: NodeDataGetter + Default {
         ..Default::default()
     };
 
-    let doc = Document::new();
-    let result = html5ever::parse_document(doc, opts).from_utf8().read_from(r);
-    match result {
-        Result::Ok(doc) => {
-            Ok(doc)
-        }
-        Result::Err(s) => {
-            Err(s.to_string())
-        }
-    }
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes).map_err(|e| Error::Io(e.to_string()))?;
+    let (html, encoding) = decode_html(&bytes, content_type);
+
+    let mut doc = html5ever::parse_document(Document::new(), opts).one(html);
+    doc.encoding = encoding.name().to_string();
+    Ok(doc)
 }
 
-pub fn parse_document<R>(r: &mut R) -> Result<Document<DefaultNodeData>, String>
+pub fn parse_document<R>(r: &mut R) -> Result<Document<DefaultNodeData>, Error>
 where R: io::Read {
     custom_parse_document::<R, DefaultNodeData>(r)
-}
\ No newline at end of file
+}
+
+pub fn parse_document_with_charset<R>(r: &mut R, content_type: Option<&str>) -> Result<Document<DefaultNodeData>, Error>
+where R: io::Read {
+    custom_parse_document_with_charset::<R, DefaultNodeData>(r, content_type)
+}
@@ -0,0 +1,552 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::Error;
+use crate::node::{Node, Handle, NodeDataGetter};
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum AttrOp {
+    Exists,
+    Equal,
+    Includes,
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SimpleSelector {
+    Universal,
+    Type(String),
+    Id(String),
+    Class(String),
+    Attr {
+        name: String,
+        op: AttrOp,
+        value: String,
+        ignore_case: bool,
+    },
+    Not(Vec<ComplexSelector>),
+    NthChild(i32, i32),
+    NthLastChild(i32, i32),
+    NthOfType(i32, i32),
+    NthLastOfType(i32, i32),
+    OnlyChild,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CompoundSelector {
+    simples: Vec<SimpleSelector>,
+}
+
+/// A sequence of compound selectors joined by combinators, stored right to left:
+/// `parts[0]` is the subject and `parts[i].0` is the combinator linking
+/// `parts[i - 1]` to `parts[i]`.
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexSelector {
+    parts: Vec<(Combinator, CompoundSelector)>,
+}
+
+/// A parsed CSS selector list, e.g. `div.article > p:not(.ad), h1`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    selectors: Vec<ComplexSelector>,
+}
+
+impl Selector {
+    pub fn parse(s: &str) -> Result<Selector, Error> {
+        let mut parser = SelectorParser { chars: s.chars().peekable() };
+        let selectors = parser.parse_selector_list(false).map_err(Error::Selector)?;
+        if parser.chars.peek().is_some() {
+            return Err(Error::Selector(format!("unexpected character in selector: {}", s)));
+        }
+        Ok(Selector { selectors })
+    }
+
+    pub fn matches<ND>(&self, node: &Node<ND>) -> bool
+    where ND: NodeDataGetter + Default {
+        node.is_element_node() && match_any(&self.selectors, node)
+    }
+}
+
+struct SelectorParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+impl<'a> SelectorParser<'a> {
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while let Some(c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+            skipped = true;
+        }
+        skipped
+    }
+
+    fn parse_ident(&mut self) -> Result<String, String> {
+        let mut ident = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '\\' {
+                self.chars.next();
+                ident.push(self.chars.next().ok_or("unexpected end of selector after '\\'")?);
+            } else if is_ident_char(c) {
+                ident.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if ident.is_empty() {
+            Err("expected identifier in selector".to_string())
+        } else {
+            Ok(ident)
+        }
+    }
+
+    fn parse_string(&mut self, quote: char) -> Result<String, String> {
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\\') => s.push(self.chars.next().ok_or("unterminated string in selector")?),
+                Some(c) if c == quote => return Ok(s),
+                Some(c) => s.push(c),
+                None => return Err("unterminated string in selector".to_string()),
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}' in selector, found '{}'", expected, c)),
+            None => Err(format!("expected '{}' in selector, found end", expected)),
+        }
+    }
+
+    fn parse_selector_list(&mut self, nested: bool) -> Result<Vec<ComplexSelector>, String> {
+        let mut selectors = vec![];
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.parse_complex()?);
+            match self.chars.peek() {
+                Some(',') => {
+                    self.chars.next();
+                },
+                Some(')') if nested => return Ok(selectors),
+                None if !nested => return Ok(selectors),
+                Some(c) => return Err(format!("unexpected '{}' in selector", c)),
+                None => return Err("unexpected end of selector".to_string()),
+            }
+        }
+    }
+
+    fn parse_combinator(&mut self) -> Option<Combinator> {
+        let whitespace = self.skip_whitespace();
+        let combinator = match self.chars.peek() {
+            Some('>') => Combinator::Child,
+            Some('+') => Combinator::NextSibling,
+            Some('~') => Combinator::SubsequentSibling,
+            Some(',') | Some(')') | None => return None,
+            Some(_) if whitespace => return Some(Combinator::Descendant),
+            Some(_) => return None,
+        };
+        self.chars.next();
+        self.skip_whitespace();
+        Some(combinator)
+    }
+
+    fn parse_complex(&mut self) -> Result<ComplexSelector, String> {
+        let mut parts = vec![(Combinator::Descendant, self.parse_compound()?)];
+        while let Some(combinator) = self.parse_combinator() {
+            parts.push((combinator, self.parse_compound()?));
+        }
+        // Store right to left so that each part carries the combinator leading
+        // to the part on its left.
+        let mut result = vec![];
+        let mut next = Combinator::Descendant;
+        for (combinator, compound) in parts.into_iter().rev() {
+            result.push((next, compound));
+            next = combinator;
+        }
+        Ok(ComplexSelector { parts: result })
+    }
+
+    fn parse_compound(&mut self) -> Result<CompoundSelector, String> {
+        let mut simples = vec![];
+        match self.chars.peek() {
+            Some('*') => {
+                self.chars.next();
+                simples.push(SimpleSelector::Universal);
+            },
+            Some(&c) if is_ident_char(c) || c == '\\' => {
+                simples.push(SimpleSelector::Type(self.parse_ident()?.to_ascii_lowercase()));
+            },
+            _ => {},
+        }
+        loop {
+            match self.chars.peek() {
+                Some('#') => {
+                    self.chars.next();
+                    simples.push(SimpleSelector::Id(self.parse_ident()?));
+                },
+                Some('.') => {
+                    self.chars.next();
+                    simples.push(SimpleSelector::Class(self.parse_ident()?));
+                },
+                Some('[') => {
+                    self.chars.next();
+                    simples.push(self.parse_attr()?);
+                },
+                Some(':') => {
+                    self.chars.next();
+                    simples.push(self.parse_pseudo()?);
+                },
+                _ => break,
+            }
+        }
+        if simples.is_empty() {
+            match self.chars.peek() {
+                Some(c) => Err(format!("unexpected '{}' in selector", c)),
+                None => Err("unexpected end of selector".to_string()),
+            }
+        } else {
+            Ok(CompoundSelector { simples })
+        }
+    }
+
+    fn parse_attr(&mut self) -> Result<SimpleSelector, String> {
+        self.skip_whitespace();
+        let name = self.parse_ident()?.to_ascii_lowercase();
+        self.skip_whitespace();
+        let op = match self.chars.next() {
+            Some(']') => return Ok(SimpleSelector::Attr {
+                name,
+                op: AttrOp::Exists,
+                value: String::new(),
+                ignore_case: false,
+            }),
+            Some('=') => AttrOp::Equal,
+            Some(c) => {
+                let op = match c {
+                    '~' => AttrOp::Includes,
+                    '|' => AttrOp::DashMatch,
+                    '^' => AttrOp::Prefix,
+                    '$' => AttrOp::Suffix,
+                    '*' => AttrOp::Substring,
+                    _ => return Err(format!("unknown attribute operator '{}' in selector", c)),
+                };
+                self.expect('=')?;
+                op
+            },
+            None => return Err("unexpected end of selector".to_string()),
+        };
+        self.skip_whitespace();
+        let value = match self.chars.peek() {
+            Some(&quote) if quote == '"' || quote == '\'' => {
+                self.chars.next();
+                self.parse_string(quote)?
+            },
+            _ => self.parse_ident()?,
+        };
+        self.skip_whitespace();
+        let ignore_case = match self.chars.peek() {
+            Some('i') | Some('I') => {
+                self.chars.next();
+                self.skip_whitespace();
+                true
+            },
+            _ => false,
+        };
+        self.expect(']')?;
+        Ok(SimpleSelector::Attr { name, op, value, ignore_case })
+    }
+
+    fn parse_arguments(&mut self) -> Result<String, String> {
+        self.expect('(')?;
+        let mut args = String::new();
+        loop {
+            match self.chars.next() {
+                Some(')') => return Ok(args.trim().to_string()),
+                Some(c) => args.push(c),
+                None => return Err("unterminated pseudo-class arguments in selector".to_string()),
+            }
+        }
+    }
+
+    fn parse_pseudo(&mut self) -> Result<SimpleSelector, String> {
+        let name = self.parse_ident()?.to_ascii_lowercase();
+        let simple = match name.as_str() {
+            "not" => {
+                self.expect('(')?;
+                let selectors = self.parse_selector_list(true)?;
+                self.expect(')')?;
+                SimpleSelector::Not(selectors)
+            },
+            "nth-child" => {
+                let (a, b) = parse_nth(&self.parse_arguments()?)?;
+                SimpleSelector::NthChild(a, b)
+            },
+            "nth-last-child" => {
+                let (a, b) = parse_nth(&self.parse_arguments()?)?;
+                SimpleSelector::NthLastChild(a, b)
+            },
+            "nth-of-type" => {
+                let (a, b) = parse_nth(&self.parse_arguments()?)?;
+                SimpleSelector::NthOfType(a, b)
+            },
+            "nth-last-of-type" => {
+                let (a, b) = parse_nth(&self.parse_arguments()?)?;
+                SimpleSelector::NthLastOfType(a, b)
+            },
+            "first-child" => SimpleSelector::NthChild(0, 1),
+            "last-child" => SimpleSelector::NthLastChild(0, 1),
+            "first-of-type" => SimpleSelector::NthOfType(0, 1),
+            "last-of-type" => SimpleSelector::NthLastOfType(0, 1),
+            "only-child" => SimpleSelector::OnlyChild,
+            "empty" => SimpleSelector::Empty,
+            _ => return Err(format!("unsupported pseudo-class in selector: {}", name)),
+        };
+        Ok(simple)
+    }
+}
+
+/// Parses the `an+b` microsyntax used by `:nth-child()` and friends.
+fn parse_nth(s: &str) -> Result<(i32, i32), String> {
+    let s = s.to_ascii_lowercase().chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    let invalid = || format!("invalid nth expression in selector: {}", s);
+    match s.as_str() {
+        "odd" => return Ok((2, 1)),
+        "even" => return Ok((2, 0)),
+        _ => {},
+    }
+    match s.find('n') {
+        Some(index) => {
+            let a = match &s[..index] {
+                "" | "+" => 1,
+                "-" => -1,
+                a => a.parse::<i32>().map_err(|_| invalid())?,
+            };
+            let b = match &s[index + 1..] {
+                "" => 0,
+                b if b.starts_with('+') || b.starts_with('-') => {
+                    b.trim_start_matches('+').parse::<i32>().map_err(|_| invalid())?
+                },
+                _ => return Err(invalid()),
+            };
+            Ok((a, b))
+        },
+        None => Ok((0, s.parse::<i32>().map_err(|_| invalid())?)),
+    }
+}
+
+fn nth_matches(a: i32, b: i32, index: i32) -> bool {
+    if a == 0 {
+        index == b
+    } else {
+        let diff = index - b;
+        diff % a == 0 && diff / a >= 0
+    }
+}
+
+fn prev_element<ND>(node: &Node<ND>) -> Option<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    let mut prev = node.prev_sibling();
+    while let Some(sibling) = prev {
+        if sibling.borrow().is_element_node() {
+            return Some(sibling);
+        }
+        prev = sibling.borrow().prev_sibling();
+    }
+    None
+}
+
+fn parent_element<ND>(node: &Node<ND>) -> Option<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    node.parent().filter(|parent| parent.borrow().is_element_node())
+}
+
+/// 1-based position of `node` among its element siblings, counted from the
+/// start or the end and optionally restricted to siblings of the same type.
+fn element_index<ND>(node: &Node<ND>, from_end: bool, same_type: bool) -> i32
+where ND: NodeDataGetter + Default {
+    let mut index = 1;
+    let mut sibling = if from_end { node.next_sibling() } else { node.prev_sibling() };
+    while let Some(current) = sibling {
+        let borrow = current.borrow();
+        if borrow.is_element_node() && (!same_type || borrow.name().local == node.name().local) {
+            index += 1;
+        }
+        sibling = if from_end { borrow.next_sibling() } else { borrow.prev_sibling() };
+    }
+    index
+}
+
+fn attr_matches(actual: &str, op: &AttrOp, expected: &str, ignore_case: bool) -> bool {
+    let (actual, expected) = if ignore_case {
+        (actual.to_lowercase(), expected.to_lowercase())
+    } else {
+        (actual.to_string(), expected.to_string())
+    };
+    match op {
+        AttrOp::Exists => true,
+        AttrOp::Equal => actual == expected,
+        AttrOp::Includes => actual.split_whitespace().any(|word| word == expected),
+        AttrOp::DashMatch => actual == expected || actual.starts_with(&(expected + "-")),
+        AttrOp::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+        AttrOp::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+        AttrOp::Substring => !expected.is_empty() && actual.contains(&expected),
+    }
+}
+
+fn match_simple<ND>(simple: &SimpleSelector, node: &Node<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    match simple {
+        SimpleSelector::Universal => true,
+        SimpleSelector::Type(name) => node.name().local.as_ref().eq_ignore_ascii_case(name),
+        SimpleSelector::Id(id) => node.attr("id").as_deref() == Some(id.as_str()),
+        SimpleSelector::Class(class) => node.attr("class")
+            .is_some_and(|classes| classes.split_whitespace().any(|c| c == class)),
+        SimpleSelector::Attr { name, op, value, ignore_case } => node.attr(name)
+            .is_some_and(|actual| attr_matches(&actual, op, value, *ignore_case)),
+        SimpleSelector::Not(selectors) => !match_any(selectors, node),
+        SimpleSelector::NthChild(a, b) => nth_matches(*a, *b, element_index(node, false, false)),
+        SimpleSelector::NthLastChild(a, b) => nth_matches(*a, *b, element_index(node, true, false)),
+        SimpleSelector::NthOfType(a, b) => nth_matches(*a, *b, element_index(node, false, true)),
+        SimpleSelector::NthLastOfType(a, b) => nth_matches(*a, *b, element_index(node, true, true)),
+        SimpleSelector::OnlyChild => element_index(node, false, false) == 1
+            && element_index(node, true, false) == 1,
+        SimpleSelector::Empty => node.children().all(|child| {
+            let child = child.borrow();
+            !child.is_element_node() && (!child.is_text_node() || child.text().is_empty())
+        }),
+    }
+}
+
+fn match_compound<ND>(compound: &CompoundSelector, node: &Node<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    compound.simples.iter().all(|simple| match_simple(simple, node))
+}
+
+fn match_complex<ND>(parts: &[(Combinator, CompoundSelector)], node: &Node<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    let (_, compound) = &parts[0];
+    if !match_compound(compound, node) {
+        return false;
+    }
+    if parts.len() == 1 {
+        return true;
+    }
+    let rest = &parts[1..];
+    match rest[0].0 {
+        Combinator::Child => parent_element(node)
+            .is_some_and(|parent| match_complex(rest, &parent.borrow())),
+        Combinator::Descendant => {
+            let mut ancestor = parent_element(node);
+            while let Some(current) = ancestor {
+                if match_complex(rest, &current.borrow()) {
+                    return true;
+                }
+                ancestor = parent_element(&current.borrow());
+            }
+            false
+        },
+        Combinator::NextSibling => prev_element(node)
+            .is_some_and(|sibling| match_complex(rest, &sibling.borrow())),
+        Combinator::SubsequentSibling => {
+            let mut sibling = prev_element(node);
+            while let Some(current) = sibling {
+                if match_complex(rest, &current.borrow()) {
+                    return true;
+                }
+                sibling = prev_element(&current.borrow());
+            }
+            false
+        },
+    }
+}
+
+fn match_any<ND>(selectors: &[ComplexSelector], node: &Node<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    selectors.iter().any(|selector| match_complex(&selector.parts, node))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_document;
+    use super::{Selector, parse_nth};
+
+    const HTML: &str = r#"<html><head><title>t</title></head><body>
+<div id="main" class="article article-body">
+  <h1>Title</h1>
+  <p class="lead">one</p>
+  <p>two</p>
+  <p class="ad">three</p>
+  <ul><li>a</li><li>b</li><li>c</li></ul>
+  <a href="https://example.com/x">x</a>
+  <a href="/relative">y</a>
+</div>
+<div class="footer"><p>four</p></div>
+</body></html>"#;
+
+    fn texts(selector: &str) -> Vec<String> {
+        let doc = parse_document(&mut HTML.as_bytes()).unwrap();
+        let document = doc.document.borrow();
+        document.select(selector).unwrap().iter()
+            .map(|node| node.borrow().descendants_text())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_nth() {
+        assert_eq!(parse_nth("odd").unwrap(), (2, 1));
+        assert_eq!(parse_nth("2n + 1").unwrap(), (2, 1));
+        assert_eq!(parse_nth("-n+3").unwrap(), (-1, 3));
+        assert_eq!(parse_nth("4").unwrap(), (0, 4));
+        assert!(parse_nth("n+").is_err());
+    }
+
+    #[test]
+    fn test_simple_and_compound() {
+        assert_eq!(texts("p"), vec!["one", "two", "three", "four"]);
+        assert_eq!(texts("p.lead"), vec!["one"]);
+        assert_eq!(texts("#main > h1"), vec!["Title"]);
+        assert_eq!(texts("[class*=article-body] p:not(.ad):not(.lead)"), vec!["two"]);
+        assert_eq!(texts("a[href^=http]"), vec!["x"]);
+        assert_eq!(texts("a[href^='/']"), vec!["y"]);
+    }
+
+    #[test]
+    fn test_combinators_and_lists() {
+        assert_eq!(texts("h1 + p"), vec!["one"]);
+        assert_eq!(texts("h1 ~ p"), vec!["one", "two", "three"]);
+        assert_eq!(texts("li:nth-child(odd)"), vec!["a", "c"]);
+        assert_eq!(texts("li:last-child, h1"), vec!["Title", "c"]);
+        assert_eq!(texts(".footer p, div > p.lead"), vec!["one", "four"]);
+    }
+
+    #[test]
+    fn test_invalid_selector() {
+        assert!(Selector::parse("div >").is_err());
+        assert!(Selector::parse("p:unknown").is_err());
+        assert!(Selector::parse("[href").is_err());
+        assert!(Selector::parse("").is_err());
+    }
+}
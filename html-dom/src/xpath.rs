@@ -0,0 +1,1245 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::node::{Node, Handle, NodeDataGetter};
+
+
+/// A node addressed by an XPath expression. Attributes are not nodes in the
+/// html-dom tree, so they are represented by their owner element and index.
+pub enum XPathNode<ND> where ND: NodeDataGetter + Default {
+    Node(Handle<ND>),
+    Attribute {
+        owner: Handle<ND>,
+        index: usize,
+    },
+}
+
+impl<ND> Clone for XPathNode<ND> where ND: NodeDataGetter + Default {
+    fn clone(&self) -> Self {
+        match self {
+            XPathNode::Node(node) => XPathNode::Node(node.clone()),
+            XPathNode::Attribute { owner, index } => XPathNode::Attribute {
+                owner: owner.clone(),
+                index: *index,
+            },
+        }
+    }
+}
+
+impl<ND> XPathNode<ND> where ND: NodeDataGetter + Default {
+    pub fn handle(&self) -> Option<&Handle<ND>> {
+        match self {
+            XPathNode::Node(node) => Some(node),
+            XPathNode::Attribute { .. } => None,
+        }
+    }
+
+    pub fn is_attribute(&self) -> bool {
+        matches!(self, XPathNode::Attribute { .. })
+    }
+
+    /// The XPath string-value of the node.
+    pub fn string_value(&self) -> String {
+        match self {
+            XPathNode::Node(node) => {
+                let node = node.borrow();
+                match &*node {
+                    Node::Text { text, .. } | Node::Comment { text, .. } => text.clone(),
+                    Node::Element { .. } | Node::Document { .. } => node.descendants()
+                        .filter(|child| child.borrow().is_text_node())
+                        .map(|child| child.borrow().text())
+                        .collect(),
+                    Node::DocType { .. } => String::new(),
+                }
+            },
+            XPathNode::Attribute { owner, index } => owner.borrow().attrs()[*index].value.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            XPathNode::Node(node) => {
+                let node = node.borrow();
+                if node.is_element_node() { node.tag_name() } else { String::new() }
+            },
+            XPathNode::Attribute { owner, index } => {
+                let owner = owner.borrow();
+                let name = &owner.attrs()[*index].name;
+                match &name.prefix {
+                    Some(prefix) => format!("{}:{}", prefix, name.local),
+                    None => name.local.to_string(),
+                }
+            },
+        }
+    }
+
+    pub fn local_name(&self) -> String {
+        match self {
+            XPathNode::Node(node) => {
+                let node = node.borrow();
+                if node.is_element_node() { node.name().local.to_string() } else { String::new() }
+            },
+            XPathNode::Attribute { owner, index } => owner.borrow().attrs()[*index].name.local.to_string(),
+        }
+    }
+
+    fn key(&self) -> (*const RefCell<Node<ND>>, usize) {
+        match self {
+            XPathNode::Node(node) => (Rc::as_ptr(node), 0),
+            XPathNode::Attribute { owner, index } => (Rc::as_ptr(owner), index + 1),
+        }
+    }
+
+    fn parent(&self) -> Option<Handle<ND>> {
+        match self {
+            XPathNode::Node(node) => node.borrow().parent(),
+            XPathNode::Attribute { owner, .. } => Some(owner.clone()),
+        }
+    }
+}
+
+/// The result of evaluating an XPath expression.
+pub enum XPathValue<ND> where ND: NodeDataGetter + Default {
+    NodeSet(Vec<XPathNode<ND>>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+fn string_to_number(s: &str) -> f64 {
+    let s = s.trim();
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let valid = !digits.is_empty() && digits != "."
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1;
+    if valid {
+        s.parse::<f64>().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() }
+    } else if n == 0.0 {
+        "0".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+impl<ND> XPathValue<ND> where ND: NodeDataGetter + Default {
+    pub fn string(&self) -> String {
+        match self {
+            XPathValue::NodeSet(nodes) => nodes.first().map(|node| node.string_value()).unwrap_or_default(),
+            XPathValue::String(s) => s.clone(),
+            XPathValue::Number(n) => number_to_string(*n),
+            XPathValue::Boolean(b) => b.to_string(),
+        }
+    }
+
+    pub fn number(&self) -> f64 {
+        match self {
+            XPathValue::Number(n) => *n,
+            XPathValue::Boolean(b) => if *b { 1.0 } else { 0.0 },
+            _ => string_to_number(&self.string()),
+        }
+    }
+
+    pub fn boolean(&self) -> bool {
+        match self {
+            XPathValue::NodeSet(nodes) => !nodes.is_empty(),
+            XPathValue::String(s) => !s.is_empty(),
+            XPathValue::Number(n) => *n != 0.0 && !n.is_nan(),
+            XPathValue::Boolean(b) => *b,
+        }
+    }
+
+    /// The element, text and comment nodes of a node-set result, in document
+    /// order. Attribute nodes and non node-set results yield nothing.
+    pub fn into_handles(self) -> Vec<Handle<ND>> {
+        match self {
+            XPathValue::NodeSet(nodes) => nodes.into_iter().filter_map(|node| match node {
+                XPathNode::Node(node) => Some(node),
+                XPathNode::Attribute { .. } => None,
+            }).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    Pipe,
+    Plus,
+    Minus,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Multiply,
+    And,
+    Or,
+    Mod,
+    Div,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    AxisName(String),
+    NodeType(String),
+    FunctionName(String),
+    NameTest(String),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+}
+
+impl Token {
+    /// Whether a following `*` or NCName must be read as an operator, see
+    /// section 3.7 of the XPath 1.0 specification.
+    fn precedes_operator(&self) -> bool {
+        !matches!(self,
+            Token::At | Token::AxisName(_) | Token::LeftParen | Token::LeftBracket | Token::Comma
+            | Token::Slash | Token::DoubleSlash | Token::Pipe | Token::Plus | Token::Minus
+            | Token::Equal | Token::NotEqual | Token::Less | Token::LessEqual | Token::Greater
+            | Token::GreaterEqual | Token::Multiply | Token::And | Token::Or | Token::Mod | Token::Div)
+    }
+}
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens: Vec<Token> = vec![];
+    let mut i = 0;
+    let read_ncname = |i: &mut usize| {
+        let start = *i;
+        while *i < chars.len() && is_name_char(chars[*i]) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>()
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let operator_context = tokens.last().is_some_and(|token| token.precedes_operator());
+        let token = match c {
+            ' ' | '\t' | '\r' | '\n' => {
+                i += 1;
+                continue;
+            },
+            '/' if next == Some('/') => { i += 2; Token::DoubleSlash },
+            '/' => { i += 1; Token::Slash },
+            '|' => { i += 1; Token::Pipe },
+            '+' => { i += 1; Token::Plus },
+            '-' => { i += 1; Token::Minus },
+            '=' => { i += 1; Token::Equal },
+            '!' if next == Some('=') => { i += 2; Token::NotEqual },
+            '<' if next == Some('=') => { i += 2; Token::LessEqual },
+            '<' => { i += 1; Token::Less },
+            '>' if next == Some('=') => { i += 2; Token::GreaterEqual },
+            '>' => { i += 1; Token::Greater },
+            '(' => { i += 1; Token::LeftParen },
+            ')' => { i += 1; Token::RightParen },
+            '[' => { i += 1; Token::LeftBracket },
+            ']' => { i += 1; Token::RightBracket },
+            '@' => { i += 1; Token::At },
+            ',' => { i += 1; Token::Comma },
+            '*' if operator_context => { i += 1; Token::Multiply },
+            '*' => { i += 1; Token::NameTest("*".to_string()) },
+            '.' if next == Some('.') => { i += 2; Token::DotDot },
+            '.' if !next.is_some_and(|c| c.is_ascii_digit()) => { i += 1; Token::Dot },
+            '"' | '\'' => {
+                let end = chars[i + 1..].iter().position(|&q| q == c)
+                    .ok_or_else(|| format!("unterminated literal in xpath: {}", s))?;
+                let literal = chars[i + 1..i + 1 + end].iter().collect();
+                i += end + 2;
+                Token::Literal(literal)
+            },
+            '$' => {
+                i += 1;
+                Token::Variable(read_ncname(&mut i))
+            },
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number = chars[start..i].iter().collect::<String>();
+                Token::Number(number.parse::<f64>().map_err(|_| format!("invalid number in xpath: {}", number))?)
+            },
+            c if is_name_start_char(c) => {
+                let mut name = read_ncname(&mut i);
+                if operator_context {
+                    match name.as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "mod" => Token::Mod,
+                        "div" => Token::Div,
+                        _ => return Err(format!("unexpected name '{}' in xpath: {}", name, s)),
+                    }
+                } else {
+                    if chars.get(i) == Some(&':') && chars.get(i + 1) != Some(&':') {
+                        i += 1;
+                        name.push(':');
+                        if chars.get(i) == Some(&'*') {
+                            i += 1;
+                            name.push('*');
+                        } else {
+                            name.push_str(&read_ncname(&mut i));
+                        }
+                    }
+                    let mut j = i;
+                    while j < chars.len() && chars[j].is_whitespace() {
+                        j += 1;
+                    }
+                    if chars.get(j) == Some(&'(') && !name.ends_with('*') {
+                        match name.as_str() {
+                            "comment" | "text" | "processing-instruction" | "node" => Token::NodeType(name),
+                            _ => Token::FunctionName(name),
+                        }
+                    } else if chars.get(j) == Some(&':') && chars.get(j + 1) == Some(&':') {
+                        i = j + 2;
+                        Token::AxisName(name)
+                    } else {
+                        Token::NameTest(name)
+                    }
+                }
+            },
+            c => return Err(format!("unexpected character '{}' in xpath: {}", c, s)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    /// The `self` axis.
+    Current,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Result<Axis, String> {
+        let axis = match name {
+            "ancestor" => Axis::Ancestor,
+            "ancestor-or-self" => Axis::AncestorOrSelf,
+            "attribute" => Axis::Attribute,
+            "child" => Axis::Child,
+            "descendant" => Axis::Descendant,
+            "descendant-or-self" => Axis::DescendantOrSelf,
+            "following" => Axis::Following,
+            "following-sibling" => Axis::FollowingSibling,
+            "parent" => Axis::Parent,
+            "preceding" => Axis::Preceding,
+            "preceding-sibling" => Axis::PrecedingSibling,
+            "self" => Axis::Current,
+            _ => return Err(format!("unsupported xpath axis: {}", name)),
+        };
+        Ok(axis)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum NodeTest {
+    Name(String),
+    Text,
+    Comment,
+    ProcessingInstruction,
+    Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Div,
+    Mod,
+    Union,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Literal(String),
+    Number(f64),
+    Function(String, Vec<Expr>),
+    Filter(Box<Expr>, Vec<Expr>),
+    /// A location path, either absolute, relative to the context node, or
+    /// relative to the node-set produced by a filter expression.
+    Path {
+        start: Option<Box<Expr>>,
+        absolute: bool,
+        steps: Vec<Step>,
+    },
+}
+
+struct XPathParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl XPathParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {:?} in xpath, found {:?}", expected, token)),
+            None => Err(format!("expected {:?} in xpath, found end", expected)),
+        }
+    }
+
+    fn parse_binary<F>(&mut self, ops: &[(Token, BinaryOp)], operand: F) -> Result<Expr, String>
+    where F: Fn(&mut XPathParser) -> Result<Expr, String> {
+        let mut left = operand(self)?;
+        while let Some(op) = self.peek().and_then(|token| ops.iter().find(|(t, _)| t == token)).map(|(_, op)| *op) {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(operand(self)?));
+        }
+        Ok(left)
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        self.parse_binary(&[(Token::Or, BinaryOp::Or)], |p| {
+            p.parse_binary(&[(Token::And, BinaryOp::And)], |p| {
+                p.parse_binary(&[(Token::Equal, BinaryOp::Equal), (Token::NotEqual, BinaryOp::NotEqual)], |p| {
+                    p.parse_binary(&[
+                        (Token::Less, BinaryOp::Less), (Token::LessEqual, BinaryOp::LessEqual),
+                        (Token::Greater, BinaryOp::Greater), (Token::GreaterEqual, BinaryOp::GreaterEqual),
+                    ], |p| {
+                        p.parse_binary(&[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Subtract)], |p| {
+                            p.parse_binary(&[
+                                (Token::Multiply, BinaryOp::Multiply), (Token::Div, BinaryOp::Div),
+                                (Token::Mod, BinaryOp::Mod),
+                            ], XPathParser::parse_unary)
+                        })
+                    })
+                })
+            })
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Minus) {
+            self.pos += 1;
+            return Ok(Expr::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_binary(&[(Token::Pipe, BinaryOp::Union)], XPathParser::parse_path)
+    }
+
+    fn parse_path(&mut self) -> Result<Expr, String> {
+        let is_primary = matches!(self.peek(),
+            Some(Token::Variable(_)) | Some(Token::LeftParen) | Some(Token::Literal(_))
+            | Some(Token::Number(_)) | Some(Token::FunctionName(_)));
+        if !is_primary {
+            return self.parse_location_path();
+        }
+        let primary = self.parse_primary()?;
+        let predicates = self.parse_predicates()?;
+        let filter = if predicates.is_empty() {
+            primary
+        } else {
+            Expr::Filter(Box::new(primary), predicates)
+        };
+        let mut steps = vec![];
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+            },
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                steps.push(descendant_or_self_step());
+            },
+            _ => return Ok(filter),
+        }
+        self.parse_relative_path(&mut steps)?;
+        Ok(Expr::Path { start: Some(Box::new(filter)), absolute: false, steps })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Variable(name)) => Err(format!("xpath variables are not supported: ${}", name)),
+            Some(Token::LeftParen) => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            },
+            Some(Token::Literal(s)) => Ok(Expr::Literal(s)),
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::FunctionName(name)) => {
+                self.expect(Token::LeftParen)?;
+                let mut args = vec![];
+                if self.peek() != Some(&Token::RightParen) {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                Ok(Expr::Function(name, args))
+            },
+            token => Err(format!("unexpected {:?} in xpath", token)),
+        }
+    }
+
+    fn parse_predicates(&mut self) -> Result<Vec<Expr>, String> {
+        let mut predicates = vec![];
+        while self.peek() == Some(&Token::LeftBracket) {
+            self.pos += 1;
+            predicates.push(self.parse_expr()?);
+            self.expect(Token::RightBracket)?;
+        }
+        Ok(predicates)
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(self.peek(),
+            Some(Token::AxisName(_)) | Some(Token::At) | Some(Token::NameTest(_))
+            | Some(Token::NodeType(_)) | Some(Token::Dot) | Some(Token::DotDot))
+    }
+
+    fn parse_location_path(&mut self) -> Result<Expr, String> {
+        let mut steps = vec![];
+        let absolute = match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+                if !self.starts_step() {
+                    return Ok(Expr::Path { start: None, absolute: true, steps });
+                }
+                true
+            },
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                steps.push(descendant_or_self_step());
+                true
+            },
+            _ => false,
+        };
+        self.parse_relative_path(&mut steps)?;
+        Ok(Expr::Path { start: None, absolute, steps })
+    }
+
+    fn parse_relative_path(&mut self, steps: &mut Vec<Step>) -> Result<(), String> {
+        loop {
+            steps.push(self.parse_step()?);
+            match self.peek() {
+                Some(Token::Slash) => {
+                    self.pos += 1;
+                },
+                Some(Token::DoubleSlash) => {
+                    self.pos += 1;
+                    steps.push(descendant_or_self_step());
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_step(&mut self) -> Result<Step, String> {
+        let axis = match self.peek() {
+            Some(Token::Dot) => {
+                self.pos += 1;
+                return Ok(Step { axis: Axis::Current, test: NodeTest::Node, predicates: vec![] });
+            },
+            Some(Token::DotDot) => {
+                self.pos += 1;
+                return Ok(Step { axis: Axis::Parent, test: NodeTest::Node, predicates: vec![] });
+            },
+            Some(Token::At) => {
+                self.pos += 1;
+                Axis::Attribute
+            },
+            Some(Token::AxisName(name)) => {
+                let axis = Axis::from_name(name)?;
+                self.pos += 1;
+                axis
+            },
+            _ => Axis::Child,
+        };
+        let test = match self.next() {
+            Some(Token::NameTest(name)) => NodeTest::Name(name),
+            Some(Token::NodeType(name)) => {
+                self.expect(Token::LeftParen)?;
+                if name == "processing-instruction" {
+                    if let Some(Token::Literal(_)) = self.peek() {
+                        self.pos += 1;
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                match name.as_str() {
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    "processing-instruction" => NodeTest::ProcessingInstruction,
+                    _ => NodeTest::Node,
+                }
+            },
+            token => return Err(format!("expected node test in xpath, found {:?}", token)),
+        };
+        let predicates = self.parse_predicates()?;
+        Ok(Step { axis, test, predicates })
+    }
+}
+
+fn descendant_or_self_step() -> Step {
+    Step { axis: Axis::DescendantOrSelf, test: NodeTest::Node, predicates: vec![] }
+}
+
+/// A compiled XPath 1.0 expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    expr: Expr,
+}
+
+struct Context<ND> where ND: NodeDataGetter + Default {
+    node: XPathNode<ND>,
+    position: usize,
+    size: usize,
+}
+
+type DocumentOrder<ND> = HashMap<*const RefCell<Node<ND>>, usize>;
+
+struct Evaluator<ND> where ND: NodeDataGetter + Default {
+    root: Handle<ND>,
+    order: RefCell<Option<DocumentOrder<ND>>>,
+}
+
+impl XPath {
+    pub fn parse(s: &str) -> Result<XPath, Error> {
+        let mut parser = XPathParser { tokens: tokenize(s).map_err(Error::XPath)?, pos: 0 };
+        let expr = parser.parse_expr().map_err(Error::XPath)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(Error::XPath(format!("unexpected {:?} in xpath: {}", parser.tokens[parser.pos], s)));
+        }
+        Ok(XPath { expr })
+    }
+
+    pub fn evaluate<ND>(&self, context: &Handle<ND>) -> Result<XPathValue<ND>, Error>
+    where ND: NodeDataGetter + Default {
+        let mut root = context.clone();
+        loop {
+            let parent = root.borrow().parent();
+            match parent {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        let evaluator = Evaluator { root, order: RefCell::new(None) };
+        let context = Context { node: XPathNode::Node(context.clone()), position: 1, size: 1 };
+        evaluator.eval(&self.expr, &context).map_err(Error::XPath)
+    }
+
+    /// Evaluates the expression and returns the matched nodes, failing if the
+    /// result is not a node-set.
+    pub fn select<ND>(&self, context: &Handle<ND>) -> Result<Vec<Handle<ND>>, Error>
+    where ND: NodeDataGetter + Default {
+        match self.evaluate(context)? {
+            value @ XPathValue::NodeSet(_) => Ok(value.into_handles()),
+            _ => Err(Error::XPath("xpath result is not a node-set".to_string())),
+        }
+    }
+}
+
+fn compare_atoms<ND>(op: BinaryOp, left: &XPathValue<ND>, right: &XPathValue<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    match op {
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            let equal = match (left, right) {
+                (XPathValue::Boolean(_), _) | (_, XPathValue::Boolean(_)) => left.boolean() == right.boolean(),
+                (XPathValue::Number(_), _) | (_, XPathValue::Number(_)) => left.number() == right.number(),
+                _ => left.string() == right.string(),
+            };
+            equal == (op == BinaryOp::Equal)
+        },
+        BinaryOp::Less => left.number() < right.number(),
+        BinaryOp::LessEqual => left.number() <= right.number(),
+        BinaryOp::Greater => left.number() > right.number(),
+        BinaryOp::GreaterEqual => left.number() >= right.number(),
+        _ => false,
+    }
+}
+
+/// Converts a node for comparison against a value of the given kind.
+fn node_as<ND>(node: &XPathNode<ND>, other: &XPathValue<ND>) -> XPathValue<ND>
+where ND: NodeDataGetter + Default {
+    match other {
+        XPathValue::Number(_) => XPathValue::Number(string_to_number(&node.string_value())),
+        _ => XPathValue::String(node.string_value()),
+    }
+}
+
+fn compare<ND>(op: BinaryOp, left: &XPathValue<ND>, right: &XPathValue<ND>) -> bool
+where ND: NodeDataGetter + Default {
+    match (left, right) {
+        (XPathValue::NodeSet(_), XPathValue::Boolean(_)) | (XPathValue::Boolean(_), XPathValue::NodeSet(_)) => {
+            compare_atoms::<ND>(op, &XPathValue::Boolean(left.boolean()), &XPathValue::Boolean(right.boolean()))
+        },
+        (XPathValue::NodeSet(lefts), XPathValue::NodeSet(rights)) => lefts.iter().any(|l| {
+            let l = XPathValue::<ND>::String(l.string_value());
+            rights.iter().any(|r| compare_atoms(op, &l, &XPathValue::String(r.string_value())))
+        }),
+        (XPathValue::NodeSet(nodes), other) => nodes.iter()
+            .any(|node| compare_atoms(op, &node_as(node, other), other)),
+        (other, XPathValue::NodeSet(nodes)) => nodes.iter()
+            .any(|node| compare_atoms(op, other, &node_as(node, other))),
+        _ => compare_atoms(op, left, right),
+    }
+}
+
+fn round(n: f64) -> f64 {
+    if n.is_nan() || n.is_infinite() {
+        n
+    } else if (-0.5..0.0).contains(&n) {
+        -0.0
+    } else {
+        (n + 0.5).floor()
+    }
+}
+
+fn substring(s: &str, start: f64, len: Option<f64>) -> String {
+    let start = round(start);
+    let end = len.map_or(f64::INFINITY, |len| start + round(len));
+    s.chars().enumerate()
+        .filter(|(i, _)| {
+            let position = (*i + 1) as f64;
+            position >= start && position < end
+        })
+        .map(|(_, c)| c)
+        .collect()
+}
+
+fn translate(s: &str, from: &str, to: &str) -> String {
+    let from = from.chars().collect::<Vec<_>>();
+    let to = to.chars().collect::<Vec<_>>();
+    s.chars().filter_map(|c| match from.iter().position(|&f| f == c) {
+        Some(index) => to.get(index).copied(),
+        None => Some(c),
+    }).collect()
+}
+
+fn children<ND>(node: &Handle<ND>) -> Vec<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    let node = node.borrow();
+    if node.has_children() { node.children().collect() } else { vec![] }
+}
+
+fn descendants<ND>(node: &Handle<ND>) -> Vec<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    let node = node.borrow();
+    if node.has_children() { node.descendants().collect() } else { vec![] }
+}
+
+fn ancestors<ND>(node: &XPathNode<ND>) -> Vec<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    let mut result = vec![];
+    let mut parent = node.parent();
+    while let Some(current) = parent {
+        parent = current.borrow().parent();
+        result.push(current);
+    }
+    result
+}
+
+fn siblings<ND>(node: &Handle<ND>, following: bool) -> Vec<Handle<ND>>
+where ND: NodeDataGetter + Default {
+    let mut result = vec![];
+    let next = |node: &Handle<ND>| if following { node.borrow().next_sibling() } else { node.borrow().prev_sibling() };
+    let mut sibling = next(node);
+    while let Some(current) = sibling {
+        sibling = next(&current);
+        result.push(current);
+    }
+    result
+}
+
+impl<ND> Evaluator<ND> where ND: NodeDataGetter + Default {
+    fn document_order(&self, node: &XPathNode<ND>) -> (usize, usize) {
+        let mut order = self.order.borrow_mut();
+        let order = order.get_or_insert_with(|| {
+            let mut order = HashMap::new();
+            order.insert(Rc::as_ptr(&self.root), 0);
+            for (index, node) in descendants(&self.root).iter().enumerate() {
+                order.insert(Rc::as_ptr(node), index + 1);
+            }
+            order
+        });
+        let (ptr, attr) = node.key();
+        (order.get(&ptr).copied().unwrap_or(usize::MAX), attr)
+    }
+
+    fn sort_and_dedup(&self, nodes: Vec<XPathNode<ND>>) -> Vec<XPathNode<ND>> {
+        let mut seen = HashSet::new();
+        let mut nodes = nodes.into_iter().filter(|node| seen.insert(node.key())).collect::<Vec<_>>();
+        nodes.sort_by_cached_key(|node| self.document_order(node));
+        nodes
+    }
+
+    fn axis_nodes(&self, axis: Axis, node: &XPathNode<ND>) -> Vec<XPathNode<ND>> {
+        let wrap = |nodes: Vec<Handle<ND>>| nodes.into_iter().map(XPathNode::Node).collect::<Vec<_>>();
+        match axis {
+            Axis::Current => return vec![node.clone()],
+            Axis::Parent => return wrap(node.parent().into_iter().collect()),
+            Axis::Ancestor => return wrap(ancestors(node)),
+            Axis::AncestorOrSelf => {
+                let mut result = vec![node.clone()];
+                result.extend(wrap(ancestors(node)));
+                return result;
+            },
+            _ => {},
+        }
+        let handle = match node {
+            XPathNode::Node(handle) => handle,
+            XPathNode::Attribute { owner, .. } => {
+                // An attribute precedes the children of its owner element.
+                let owner = XPathNode::Node(owner.clone());
+                return match axis {
+                    Axis::Following => {
+                        let mut result = self.axis_nodes(Axis::Descendant, &owner);
+                        result.extend(self.axis_nodes(Axis::Following, &owner));
+                        result
+                    },
+                    Axis::Preceding => self.axis_nodes(Axis::Preceding, &owner),
+                    _ => vec![],
+                };
+            },
+        };
+        match axis {
+            Axis::Child => wrap(children(handle)),
+            Axis::Descendant => wrap(descendants(handle)),
+            Axis::DescendantOrSelf => {
+                let mut result = vec![node.clone()];
+                result.extend(wrap(descendants(handle)));
+                result
+            },
+            Axis::FollowingSibling => wrap(siblings(handle, true)),
+            Axis::PrecedingSibling => wrap(siblings(handle, false)),
+            Axis::Following => {
+                let mut result = vec![];
+                let mut current = Some(handle.clone());
+                while let Some(node) = current {
+                    for sibling in siblings(&node, true) {
+                        result.push(XPathNode::Node(sibling.clone()));
+                        result.extend(wrap(descendants(&sibling)));
+                    }
+                    current = node.borrow().parent();
+                }
+                result
+            },
+            Axis::Preceding => {
+                // Reverse document order, excluding ancestors.
+                let mut result = vec![];
+                let mut current = Some(handle.clone());
+                while let Some(node) = current {
+                    for sibling in siblings(&node, false) {
+                        result.extend(wrap(descendants(&sibling)).into_iter().rev());
+                        result.push(XPathNode::Node(sibling));
+                    }
+                    current = node.borrow().parent();
+                }
+                result
+            },
+            Axis::Attribute => {
+                let borrow = handle.borrow();
+                if borrow.is_element_node() {
+                    (0..borrow.attrs().len())
+                        .map(|index| XPathNode::Attribute { owner: handle.clone(), index })
+                        .collect()
+                } else {
+                    vec![]
+                }
+            },
+            Axis::Current | Axis::Parent | Axis::Ancestor | Axis::AncestorOrSelf => unreachable!(),
+        }
+    }
+
+    fn test_node(&self, axis: Axis, test: &NodeTest, node: &XPathNode<ND>) -> bool {
+        match (test, node) {
+            (NodeTest::Node, _) => true,
+            (NodeTest::Name(name), XPathNode::Attribute { owner, index }) => {
+                axis == Axis::Attribute && (name == "*"
+                    || owner.borrow().attrs()[*index].name.local.as_ref().eq_ignore_ascii_case(name))
+            },
+            (_, XPathNode::Attribute { .. }) => false,
+            (test, XPathNode::Node(handle)) => {
+                let node = handle.borrow();
+                match test {
+                    NodeTest::Text => node.is_text_node(),
+                    NodeTest::Comment => matches!(*node, Node::Comment { .. }),
+                    NodeTest::ProcessingInstruction => false,
+                    NodeTest::Name(name) => {
+                        if axis == Axis::Attribute || !node.is_element_node() {
+                            false
+                        } else if name == "*" {
+                            true
+                        } else if let Some(prefix) = name.strip_suffix(":*") {
+                            node.name().prefix.as_ref().is_some_and(|p| p.as_ref() == prefix)
+                        } else if name.contains(':') {
+                            node.tag_name() == *name
+                        } else {
+                            node.name().local.as_ref().eq_ignore_ascii_case(name)
+                        }
+                    },
+                    NodeTest::Node => true,
+                }
+            },
+        }
+    }
+
+    fn filter_predicates(&self, nodes: Vec<XPathNode<ND>>, predicates: &[Expr]) -> Result<Vec<XPathNode<ND>>, String> {
+        let mut nodes = nodes;
+        for predicate in predicates {
+            let size = nodes.len();
+            let mut kept = vec![];
+            for (index, node) in nodes.into_iter().enumerate() {
+                let context = Context { node, position: index + 1, size };
+                let keep = match self.eval(predicate, &context)? {
+                    XPathValue::Number(n) => n == context.position as f64,
+                    value => value.boolean(),
+                };
+                if keep {
+                    kept.push(context.node);
+                }
+            }
+            nodes = kept;
+        }
+        Ok(nodes)
+    }
+
+    fn eval_steps(&self, mut nodes: Vec<XPathNode<ND>>, steps: &[Step]) -> Result<Vec<XPathNode<ND>>, String> {
+        for step in steps {
+            let mut result = vec![];
+            for node in &nodes {
+                // Axis nodes come back in axis order, so positions in the
+                // predicates count backwards on reverse axes.
+                let candidates = self.axis_nodes(step.axis, node).into_iter()
+                    .filter(|candidate| self.test_node(step.axis, &step.test, candidate))
+                    .collect::<Vec<_>>();
+                result.extend(self.filter_predicates(candidates, &step.predicates)?);
+            }
+            nodes = self.sort_and_dedup(result);
+        }
+        Ok(nodes)
+    }
+
+    fn eval_node_set(&self, expr: &Expr, context: &Context<ND>) -> Result<Vec<XPathNode<ND>>, String> {
+        match self.eval(expr, context)? {
+            XPathValue::NodeSet(nodes) => Ok(nodes),
+            _ => Err("xpath expression is not a node-set".to_string()),
+        }
+    }
+
+    fn eval(&self, expr: &Expr, context: &Context<ND>) -> Result<XPathValue<ND>, String> {
+        let value = match expr {
+            Expr::Literal(s) => XPathValue::String(s.clone()),
+            Expr::Number(n) => XPathValue::Number(*n),
+            Expr::Negate(expr) => XPathValue::Number(-self.eval(expr, context)?.number()),
+            Expr::Binary(op, left, right) => match op {
+                BinaryOp::Or => XPathValue::Boolean(
+                    self.eval(left, context)?.boolean() || self.eval(right, context)?.boolean()),
+                BinaryOp::And => XPathValue::Boolean(
+                    self.eval(left, context)?.boolean() && self.eval(right, context)?.boolean()),
+                BinaryOp::Union => {
+                    let mut nodes = self.eval_node_set(left, context)?;
+                    nodes.extend(self.eval_node_set(right, context)?);
+                    XPathValue::NodeSet(self.sort_and_dedup(nodes))
+                },
+                BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+                | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                    XPathValue::Boolean(compare(*op, &self.eval(left, context)?, &self.eval(right, context)?))
+                },
+                _ => {
+                    let l = self.eval(left, context)?.number();
+                    let r = self.eval(right, context)?.number();
+                    XPathValue::Number(match op {
+                        BinaryOp::Add => l + r,
+                        BinaryOp::Subtract => l - r,
+                        BinaryOp::Multiply => l * r,
+                        BinaryOp::Div => l / r,
+                        _ => l % r,
+                    })
+                },
+            },
+            Expr::Filter(primary, predicates) => {
+                let nodes = self.eval_node_set(primary, context)?;
+                XPathValue::NodeSet(self.filter_predicates(nodes, predicates)?)
+            },
+            Expr::Path { start, absolute, steps } => {
+                let nodes = match start {
+                    Some(start) => self.eval_node_set(start, context)?,
+                    None if *absolute => vec![XPathNode::Node(self.root.clone())],
+                    None => vec![context.node.clone()],
+                };
+                XPathValue::NodeSet(self.eval_steps(nodes, steps)?)
+            },
+            Expr::Function(name, args) => self.eval_function(name, args, context)?,
+        };
+        Ok(value)
+    }
+
+    fn eval_function(&self, name: &str, args: &[Expr], context: &Context<ND>) -> Result<XPathValue<ND>, String> {
+        let arg = |index: usize| -> Result<XPathValue<ND>, String> {
+            match args.get(index) {
+                Some(expr) => self.eval(expr, context),
+                None => Ok(XPathValue::NodeSet(vec![context.node.clone()])),
+            }
+        };
+        let check_args = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(format!("wrong number of arguments for xpath function {}()", name))
+            } else {
+                Ok(())
+            }
+        };
+        let value = match name {
+            "last" => {
+                check_args(0, 0)?;
+                XPathValue::Number(context.size as f64)
+            },
+            "position" => {
+                check_args(0, 0)?;
+                XPathValue::Number(context.position as f64)
+            },
+            "count" => {
+                check_args(1, 1)?;
+                XPathValue::Number(self.eval_node_set(&args[0], context)?.len() as f64)
+            },
+            "id" => {
+                check_args(1, 1)?;
+                let ids = match arg(0)? {
+                    XPathValue::NodeSet(nodes) => nodes.iter().map(|node| node.string_value()).collect::<Vec<_>>().join(" "),
+                    value => value.string(),
+                };
+                let ids = ids.split_whitespace().collect::<HashSet<_>>();
+                let nodes = descendants(&self.root).into_iter()
+                    .filter(|node| {
+                        let node = node.borrow();
+                        node.is_element_node() && node.attr("id").is_some_and(|id| ids.contains(id.as_str()))
+                    })
+                    .map(XPathNode::Node)
+                    .collect();
+                XPathValue::NodeSet(nodes)
+            },
+            "local-name" | "name" | "namespace-uri" => {
+                check_args(0, 1)?;
+                let nodes = match arg(0)? {
+                    XPathValue::NodeSet(nodes) => nodes,
+                    _ => return Err(format!("argument of xpath function {}() is not a node-set", name)),
+                };
+                XPathValue::String(nodes.first().map(|node| match name {
+                    "local-name" => node.local_name(),
+                    "name" => node.name(),
+                    _ => String::new(),
+                }).unwrap_or_default())
+            },
+            "string" => {
+                check_args(0, 1)?;
+                XPathValue::String(arg(0)?.string())
+            },
+            "concat" => {
+                if args.len() < 2 {
+                    return Err("wrong number of arguments for xpath function concat()".to_string());
+                }
+                let mut s = String::new();
+                for index in 0..args.len() {
+                    s.push_str(&arg(index)?.string());
+                }
+                XPathValue::String(s)
+            },
+            "starts-with" | "contains" | "substring-before" | "substring-after" => {
+                check_args(2, 2)?;
+                let s = arg(0)?.string();
+                let pattern = arg(1)?.string();
+                match name {
+                    "starts-with" => XPathValue::Boolean(s.starts_with(&pattern)),
+                    "contains" => XPathValue::Boolean(s.contains(&pattern)),
+                    "substring-before" => XPathValue::String(
+                        s.find(&pattern).map(|index| s[..index].to_string()).unwrap_or_default()),
+                    _ => XPathValue::String(
+                        s.find(&pattern).map(|index| s[index + pattern.len()..].to_string()).unwrap_or_default()),
+                }
+            },
+            "substring" => {
+                check_args(2, 3)?;
+                let len = if args.len() == 3 { Some(arg(2)?.number()) } else { None };
+                XPathValue::String(substring(&arg(0)?.string(), arg(1)?.number(), len))
+            },
+            "string-length" => {
+                check_args(0, 1)?;
+                XPathValue::Number(arg(0)?.string().chars().count() as f64)
+            },
+            "normalize-space" => {
+                check_args(0, 1)?;
+                XPathValue::String(arg(0)?.string().split_whitespace().collect::<Vec<_>>().join(" "))
+            },
+            "translate" => {
+                check_args(3, 3)?;
+                XPathValue::String(translate(&arg(0)?.string(), &arg(1)?.string(), &arg(2)?.string()))
+            },
+            "boolean" => {
+                check_args(1, 1)?;
+                XPathValue::Boolean(arg(0)?.boolean())
+            },
+            "not" => {
+                check_args(1, 1)?;
+                XPathValue::Boolean(!arg(0)?.boolean())
+            },
+            "true" | "false" => {
+                check_args(0, 0)?;
+                XPathValue::Boolean(name == "true")
+            },
+            "lang" => {
+                check_args(1, 1)?;
+                let lang = arg(0)?.string().to_lowercase();
+                let mut nodes = vec![context.node.clone()];
+                nodes.extend(ancestors(&context.node).into_iter().map(XPathNode::Node));
+                let value = nodes.iter().filter_map(|node| node.handle())
+                    .filter(|node| node.borrow().is_element_node())
+                    .find_map(|node| node.borrow().attr("xml:lang").or_else(|| node.borrow().attr("lang")))
+                    .map(|value| value.to_lowercase());
+                XPathValue::Boolean(value.is_some_and(|value| {
+                    value == lang || value.starts_with(&(lang + "-"))
+                }))
+            },
+            "number" => {
+                check_args(0, 1)?;
+                XPathValue::Number(arg(0)?.number())
+            },
+            "sum" => {
+                check_args(1, 1)?;
+                XPathValue::Number(self.eval_node_set(&args[0], context)?.iter()
+                    .map(|node| string_to_number(&node.string_value()))
+                    .sum())
+            },
+            "floor" | "ceiling" | "round" => {
+                check_args(1, 1)?;
+                let n = arg(0)?.number();
+                XPathValue::Number(match name {
+                    "floor" => n.floor(),
+                    "ceiling" => n.ceil(),
+                    _ => round(n),
+                })
+            },
+            _ => return Err(format!("unsupported xpath function: {}()", name)),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_document;
+    use super::{XPath, XPathValue};
+
+    const HTML: &str = r#"<html><head><title>Title</title></head><body>
+<div id="main" class="article"><p class="lead">one</p><p>two</p><p>three <b>bold</b></p>
+<a href="/a">first</a><a href="https://example.com/b">second</a></div>
+<div class="footer"><p>four</p></div>
+</body></html>"#;
+
+    fn evaluate(expr: &str) -> String {
+        let doc = parse_document(&mut HTML.as_bytes()).unwrap();
+        match XPath::parse(expr).unwrap().evaluate(&doc.document).unwrap() {
+            XPathValue::NodeSet(nodes) => nodes.iter()
+                .map(|node| node.string_value())
+                .collect::<Vec<_>>().join("|"),
+            value => value.string(),
+        }
+    }
+
+    #[test]
+    fn test_paths_and_predicates() {
+        assert_eq!(evaluate("//div[@id='main']/p"), "one|two|three bold");
+        assert_eq!(evaluate("//p[2]"), "two");
+        assert_eq!(evaluate("//div[@class='article']/p[last()]/text()"), "three ");
+        assert_eq!(evaluate("(//p)[position() > 2]"), "three bold|four");
+        assert_eq!(evaluate("//a[starts-with(@href, 'http')]/@href"), "https://example.com/b");
+        assert_eq!(evaluate("//b/ancestor::div/@id"), "main");
+        assert_eq!(evaluate("//p[contains(., 'bold')]/preceding-sibling::p[1]"), "two");
+        assert_eq!(evaluate("//p[@class='lead']/following::p"), "two|three bold|four");
+        assert_eq!(evaluate("/html/head/title | //div[2]/p"), "Title|four");
+    }
+
+    #[test]
+    fn test_functions_and_operators() {
+        assert_eq!(evaluate("count(//p)"), "4");
+        assert_eq!(evaluate("count(//p) * 2 + 1 div 2"), "8.5");
+        assert_eq!(evaluate("string(//title)"), "Title");
+        assert_eq!(evaluate("normalize-space(concat('  a ', ' b  '))"), "a b");
+        assert_eq!(evaluate("substring('12345', 1.5, 2.6)"), "234");
+        assert_eq!(evaluate("translate('bar', 'abc', 'ABC')"), "BAr");
+        assert_eq!(evaluate("//p = 'two'"), "true");
+        assert_eq!(evaluate("count(//a[@href]) mod 2 = 0 and not(//table)"), "true");
+        assert_eq!(evaluate("round(-2.5)"), "-2");
+        assert_eq!(evaluate("local-name(//*[@id])"), "div");
+    }
+
+    #[test]
+    fn test_invalid_expression() {
+        assert!(XPath::parse("//div[").is_err());
+        assert!(XPath::parse("//div/unknown::p").is_err());
+        assert!(XPath::parse("$var").is_err());
+        let doc = parse_document(&mut HTML.as_bytes()).unwrap();
+        assert!(XPath::parse("foo()").unwrap().evaluate(&doc.document).is_err());
+        assert!(XPath::parse("count(1)").unwrap().evaluate(&doc.document).is_err());
+    }
+}
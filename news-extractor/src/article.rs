@@ -0,0 +1,232 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use render_dom::{Handle, Node};
+
+use crate::ld_json::LdJson;
+use crate::metadata::{Metadata, MetaTags};
+use crate::parser::normalize;
+
+
+/// A byline longer than this is a paragraph that happens to mention an author.
+const MAX_BYLINE_LENGTH: usize = 100;
+
+/// Article metadata gathered from JSON-LD, `<meta>` tags and the page markup.
+#[derive(Default, Debug)]
+pub(crate) struct ArticleMeta {
+    pub authors: Vec<String>,
+    pub published_time: String,
+    pub modified_time: String,
+    pub section: String,
+    pub keywords: Vec<String>,
+    pub language: String,
+    pub publisher: String,
+}
+
+fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://") || s.starts_with("//")
+}
+
+static BYLINE_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*(by|from)\b[:\s]*").unwrap());
+static AUTHOR_SEPARATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\s*(,|;|\||\band\b|&)\s*").unwrap());
+
+/// Strips a leading "By" and splits a byline such as "By Jane Doe and John Roe"
+/// into author names.
+pub(crate) fn split_authors(byline: &str) -> Vec<String> {
+    let byline = BYLINE_PREFIX.replace(byline, "");
+    AUTHOR_SEPARATOR
+        .split(&byline)
+        .map(normalize)
+        .filter(|name| !name.is_empty() && !is_url(name))
+        .collect()
+}
+
+fn push_unique(values: &mut Vec<String>, value: String) {
+    if !values.iter().any(|v| v.eq_ignore_ascii_case(&value)) {
+        values.push(value);
+    }
+}
+
+fn apply_ld_json(meta: &mut ArticleMeta, ld_json: &LdJson) {
+    if let Some(article) = ld_json.article() {
+        for name in article.authors.iter().flat_map(|name| split_authors(name)) {
+            push_unique(&mut meta.authors, name);
+        }
+        meta.published_time = article.date_published;
+        meta.modified_time = article.date_modified;
+        meta.section = article.section;
+        for keyword in article.keywords {
+            push_unique(&mut meta.keywords, keyword);
+        }
+        meta.language = article.language;
+        meta.publisher = article.publisher.map(|publisher| publisher.name).unwrap_or_default();
+    }
+    if meta.section.is_empty() {
+        // The crumb before the article itself names its section.
+        if let Some(breadcrumbs) = ld_json.breadcrumbs() {
+            if breadcrumbs.items.len() >= 3 {
+                meta.section = breadcrumbs.items[breadcrumbs.items.len() - 2].name.clone();
+            }
+        }
+    }
+    if meta.publisher.is_empty() {
+        meta.publisher = ld_json.organization().map(|organization| organization.name).unwrap_or_default();
+    }
+}
+
+fn apply_meta_tags(meta: &mut ArticleMeta, tags: &MetaTags) {
+    if meta.authors.is_empty() {
+        for byline in tags.all(&["article:author", "author", "dc.creator", "dcterms.creator", "byl", "parsely-author", "sailthru.author"]) {
+            for name in split_authors(&byline) {
+                push_unique(&mut meta.authors, name);
+            }
+        }
+    }
+    let fill = |value: &mut String, keys: &[&str]| {
+        if value.is_empty() {
+            *value = tags.first(keys).unwrap_or_default();
+        }
+    };
+    fill(&mut meta.published_time, &["article:published_time", "datepublished", "dc.date.issued", "dcterms.issued",
+        "dcterms.created", "dc.date", "pubdate", "publishdate", "parsely-pub-date", "sailthru.date"]);
+    fill(&mut meta.modified_time, &["article:modified_time", "datemodified", "og:updated_time", "dcterms.modified",
+        "dc.date.modified", "lastmod"]);
+    fill(&mut meta.section, &["article:section", "section", "parsely-section", "dc.subject"]);
+    fill(&mut meta.language, &["dc.language", "dcterms.language", "content-language", "og:locale"]);
+    if meta.publisher.is_empty() {
+        meta.publisher = tags.all(&["og:site_name", "dc.publisher", "dcterms.publisher", "article:publisher", "application-name"])
+            .into_iter().find(|publisher| !is_url(publisher)).unwrap_or_default();
+    }
+    if meta.keywords.is_empty() {
+        for keyword in tags.all(&["article:tag", "news_keywords", "keywords", "parsely-tags"]).iter().flat_map(|s| s.split(',')) {
+            let keyword = normalize(keyword);
+            if !keyword.is_empty() {
+                push_unique(&mut meta.keywords, keyword);
+            }
+        }
+    }
+}
+
+/// `datetime` of the `<time>` element marked as the publication or update
+/// date, falling back to the first `<time>` on the page for publication.
+fn find_time(root: &Handle, modified: bool) -> Option<String> {
+    let pattern = if modified { "(?i)updated|modified" } else { "(?i)publish|posted|created" };
+    let hint = Regex::new(pattern).unwrap();
+    let times = root.borrow().select("time[datetime]").ok()?;
+    let datetime = |node: &Handle| node.borrow().attr("datetime").map(|s| normalize(&s)).filter(|s| !s.is_empty());
+    let marked = times.iter().find(|time| {
+        let time = time.borrow();
+        let itemprop = if modified { "dateModified" } else { "datePublished" };
+        time.attr("itemprop").as_deref() == Some(itemprop)
+            || (!modified && time.attr("pubdate").is_some())
+            || ["class", "id"].iter().filter_map(|attr| time.attr(attr)).any(|s| hint.is_match(&s))
+    });
+    match marked {
+        Some(time) => datetime(time),
+        None if !modified => times.iter().find_map(datetime),
+        None => None,
+    }
+}
+
+fn byline_text(node: &Node) -> String {
+    normalize(&node.descendants_text_skip(|node| node.is_element_node() && node.is_none_tag()))
+}
+
+/// Author names from bylines in the markup: `rel="author"` links, `itemprop`
+/// authors and short elements whose class or id mentions the byline.
+fn find_byline_authors(root: &Handle) -> Vec<String> {
+    let hint = Regex::new(r"(?i)byline|author|writer").unwrap();
+    let mut authors = vec![];
+    let root = root.borrow();
+    for selector in ["[rel~=author]", "[itemprop~=author] [itemprop=name]", "[itemprop~=author]"] {
+        for node in root.select(selector).unwrap_or_default() {
+            let text = byline_text(&node.borrow());
+            if text.chars().count() <= MAX_BYLINE_LENGTH {
+                split_authors(&text).into_iter().for_each(|name| push_unique(&mut authors, name));
+            }
+        }
+        if !authors.is_empty() {
+            return authors;
+        }
+    }
+    let byline = root.descendants_skip(|node| node.is_element_node() && node.is_none_tag()).find(|node| {
+        let node = node.borrow();
+        node.is_element_node() && node.tag_name() != "meta"
+            && ["class", "id"].iter().filter_map(|attr| node.attr(attr)).any(|s| hint.is_match(&s))
+            && (1..=MAX_BYLINE_LENGTH).contains(&byline_text(&node).chars().count())
+    });
+    if let Some(byline) = byline {
+        split_authors(&byline_text(&byline.borrow())).into_iter().for_each(|name| push_unique(&mut authors, name));
+    }
+    authors
+}
+
+/// Collects article metadata, preferring JSON-LD, then `<meta>` tags (OpenGraph,
+/// `article:*` and Dublin Core), then `<time>` elements and bylines in the page.
+pub(crate) fn find_article_meta(root: &Handle, ld_json: &LdJson, metadata: &Metadata) -> ArticleMeta {
+    let mut meta = ArticleMeta::default();
+    apply_ld_json(&mut meta, ld_json);
+    apply_meta_tags(&mut meta, &metadata.tags);
+    if meta.published_time.is_empty() {
+        meta.published_time = find_time(root, false).unwrap_or_default();
+    }
+    if meta.modified_time.is_empty() {
+        meta.modified_time = find_time(root, true).unwrap_or_default();
+    }
+    if meta.authors.is_empty() {
+        meta.authors = find_byline_authors(root);
+    }
+    if meta.language.is_empty() {
+        meta.language = root.borrow().attr("lang").map(|s| normalize(&s)).unwrap_or_default();
+    }
+    meta
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::find_article_meta;
+    use crate::ld_json::LdJson;
+    use crate::metadata::Metadata;
+
+    #[test]
+    fn test_ld_json_and_meta() {
+        let html = r#"<html lang="en"><head>
+<script type="application/ld+json">{"@type": "NewsArticle", "author": [{"@type": "Person", "name": "Jane Doe"}, {"name": "John Roe"}],
+  "datePublished": "2022-03-01T10:00:00Z", "keywords": "economy, markets", "publisher": {"@type": "Organization", "name": "Daily News"}}</script>
+<meta property="article:modified_time" content="2022-03-02T08:00:00Z">
+<meta property="article:section" content="Business">
+<meta property="article:author" content="https://example.com/jane">
+<meta name="dc.language" content="en-US">
+</head><body><p class="byline">By Someone Else</p></body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let meta = find_article_meta(&root, &LdJson::new(&root), &Metadata::new(&root));
+        assert_eq!(meta.authors, vec!["Jane Doe", "John Roe"]);
+        assert_eq!(meta.published_time, "2022-03-01T10:00:00Z");
+        assert_eq!(meta.modified_time, "2022-03-02T08:00:00Z");
+        assert_eq!(meta.section, "Business");
+        assert_eq!(meta.keywords, vec!["economy", "markets"]);
+        assert_eq!(meta.language, "en-US");
+        assert_eq!(meta.publisher, "Daily News");
+    }
+
+    #[test]
+    fn test_markup_fallbacks() {
+        let html = r#"<html lang="fr"><head><meta property="og:site_name" content="Le Journal">
+<meta property="article:tag" content="Politique"><meta property="article:tag" content="Europe"></head><body>
+<div class="article-byline">By <a href="/marie">Marie Curie</a> and <a href="/pierre">Pierre Curie</a></div>
+<time datetime="2021-05-04" class="date-updated">4 mai</time>
+<time datetime="2021-05-03" pubdate>3 mai</time>
+</body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let meta = find_article_meta(&root, &LdJson::new(&root), &Metadata::new(&root));
+        assert_eq!(meta.authors, vec!["Marie Curie", "Pierre Curie"]);
+        assert_eq!(meta.published_time, "2021-05-03");
+        assert_eq!(meta.modified_time, "2021-05-04");
+        assert_eq!(meta.keywords, vec!["Politique", "Europe"]);
+        assert_eq!(meta.language, "fr");
+        assert_eq!(meta.publisher, "Le Journal");
+    }
+}
@@ -0,0 +1,330 @@
+use render_dom::{Handle, Node};
+use serde::{Serialize, Deserialize};
+
+use crate::urls::UrlResolver;
+
+
+/// Kind of an inline span.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanKind {
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+}
+
+/// Inline markup over `text`, as a range of character (not byte) offsets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Span {
+    pub kind: SpanKind,
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub href: Option<String>,
+}
+
+/// Text with its inline spans. Whitespace is collapsed and `<br>` and nested
+/// blocks become line breaks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Inline {
+    pub text: String,
+    pub spans: Vec<Span>,
+}
+
+/// A block of the article content.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph {
+        #[serde(flatten)]
+        inline: Inline,
+    },
+    Heading {
+        level: u8,
+        #[serde(flatten)]
+        inline: Inline,
+    },
+    Quote {
+        #[serde(flatten)]
+        inline: Inline,
+    },
+    List {
+        ordered: bool,
+        items: Vec<Inline>,
+    },
+    Table {
+        rows: Vec<Vec<String>>,
+    },
+    Preformatted {
+        text: String,
+    },
+}
+
+impl Inline {
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn ends_with_space(&self) -> bool {
+        self.text.is_empty() || self.text.ends_with(' ') || self.text.ends_with('\n')
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for (i, word) in text.split_whitespace().enumerate() {
+            let leading = i > 0 || text.starts_with(char::is_whitespace);
+            if leading && !self.ends_with_space() {
+                self.text.push(' ');
+            }
+            self.text.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) && !self.ends_with_space() {
+            self.text.push(' ');
+        }
+    }
+
+    fn trim_end(&mut self) {
+        while self.text.ends_with(' ') || self.text.ends_with('\n') {
+            self.text.pop();
+        }
+        let len = self.len();
+        for span in self.spans.iter_mut() {
+            span.end = span.end.min(len);
+        }
+    }
+
+    fn push_break(&mut self) {
+        self.trim_end();
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+    }
+
+    fn finish(mut self) -> Inline {
+        self.trim_end();
+        self.spans.retain(|span| span.start < span.end);
+        self
+    }
+}
+
+/// Builds the blocks of a content node, leaving out the nodes `skip` rejects.
+pub(crate) struct BlockBuilder<'a, F> where F: Fn(&Node) -> bool {
+    skip: F,
+    resolver: &'a UrlResolver,
+    blocks: Vec<Block>,
+    loose: Inline,
+}
+
+fn heading_level(tag: &str) -> Option<u8> {
+    match tag {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_inline(node: &Node) -> bool {
+    !node.is_element_node() || node.is_inline_tag()
+}
+
+impl<'a, F> BlockBuilder<'a, F> where F: Fn(&Node) -> bool {
+    pub(crate) fn new(skip: F, resolver: &'a UrlResolver) -> Self {
+        BlockBuilder { skip, resolver, blocks: vec![], loose: Inline::default() }
+    }
+
+    pub(crate) fn build(mut self, node: &Handle) -> Vec<Block> {
+        self.visit_children(node);
+        self.flush();
+        self.blocks
+    }
+
+    /// Turns text found directly inside container elements into a paragraph.
+    fn flush(&mut self) {
+        let inline = std::mem::take(&mut self.loose).finish();
+        if !inline.text.is_empty() {
+            self.blocks.push(Block::Paragraph { inline });
+        }
+    }
+
+    fn visit_children(&mut self, node: &Handle) {
+        for child in node.borrow().children() {
+            self.visit(&child);
+        }
+    }
+
+    fn visit(&mut self, handle: &Handle) {
+        let node = handle.borrow();
+        if node.is_element_node() && (self.skip)(&node) {
+            return;
+        }
+        if is_inline(&node) {
+            let mut loose = std::mem::take(&mut self.loose);
+            self.collect_inline(handle, &mut loose);
+            self.loose = loose;
+            return;
+        }
+        self.flush();
+        let tag = node.tag_name();
+        let block = match tag.as_str() {
+            "p" => Some(Block::Paragraph { inline: self.inline(handle) }),
+            "blockquote" => Some(Block::Quote { inline: self.inline(handle) }),
+            "pre" => Some(Block::Preformatted {
+                text: node.descendants_text_skip(|node| node.is_element_node() && (self.skip)(node)).trim_end().to_string(),
+            }),
+            "ul" | "ol" => Some(Block::List {
+                ordered: tag == "ol",
+                items: self.children_by_tag(handle, &["li"]).iter()
+                    .map(|item| self.inline(item))
+                    .filter(|item| !item.text.is_empty())
+                    .collect(),
+            }),
+            "table" => Some(Block::Table { rows: self.table_rows(handle) }),
+            tag => heading_level(tag).map(|level| Block::Heading { level, inline: self.inline(handle) }),
+        };
+        drop(node);
+        match block {
+            Some(block) if !is_empty(&block) => self.blocks.push(block),
+            Some(_) => {},
+            None => {
+                self.visit_children(handle);
+                self.flush();
+            },
+        }
+    }
+
+    fn children_by_tag(&self, handle: &Handle, tags: &[&str]) -> Vec<Handle> {
+        handle.borrow().children()
+            .filter(|child| {
+                let child = child.borrow();
+                child.is_element_node() && tags.contains(&child.tag_name().as_str()) && !(self.skip)(&child)
+            })
+            .collect()
+    }
+
+    fn table_rows(&self, table: &Handle) -> Vec<Vec<String>> {
+        let mut rows = self.children_by_tag(table, &["tr"]);
+        for section in self.children_by_tag(table, &["thead", "tbody", "tfoot"]) {
+            rows.extend(self.children_by_tag(&section, &["tr"]));
+        }
+        rows.iter()
+            .map(|row| self.children_by_tag(row, &["th", "td"]).iter().map(|cell| self.inline(cell).text).collect::<Vec<_>>())
+            .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+            .collect()
+    }
+
+    fn inline(&self, handle: &Handle) -> Inline {
+        let mut inline = Inline::default();
+        for child in handle.borrow().children() {
+            self.collect_inline(&child, &mut inline);
+        }
+        inline.finish()
+    }
+
+    fn collect_inline(&self, handle: &Handle, inline: &mut Inline) {
+        let node = handle.borrow();
+        if node.is_text_node() {
+            inline.push_text(&node.text());
+            return;
+        }
+        if !node.is_element_node() || (self.skip)(&node) {
+            return;
+        }
+        let tag = node.tag_name();
+        let (kind, href) = match tag.as_str() {
+            "br" => {
+                inline.push_break();
+                return;
+            },
+            "em" | "i" => (Some(SpanKind::Emphasis), None),
+            "strong" | "b" => (Some(SpanKind::Strong), None),
+            "code" => (Some(SpanKind::Code), None),
+            "a" => match node.attr("href").map(|href| self.resolver.resolve(&href)) {
+                Some(href) if !href.is_empty() && !href.starts_with("javascript:") => (Some(SpanKind::Link), Some(href)),
+                _ => (None, None),
+            },
+            _ => (None, None),
+        };
+        let block = !is_inline(&node);
+        if block {
+            inline.push_break();
+        }
+        let start = inline.len();
+        for child in node.children() {
+            self.collect_inline(&child, inline);
+        }
+        if block {
+            inline.push_break();
+        }
+        if let Some(kind) = kind {
+            // Spans cover the words only, not the space that may follow them.
+            let end = inline.text.trim_end().chars().count();
+            if end > start {
+                inline.spans.push(Span { kind, start, end, href });
+            }
+        }
+    }
+}
+
+fn is_empty(block: &Block) -> bool {
+    match block {
+        Block::Paragraph { inline } | Block::Heading { inline, .. } | Block::Quote { inline } => inline.text.is_empty(),
+        Block::List { items, .. } => items.is_empty(),
+        Block::Table { rows } => rows.is_empty(),
+        Block::Preformatted { text } => text.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::{Block, BlockBuilder, Inline, Span, SpanKind};
+    use crate::urls::UrlResolver;
+
+    fn build(html: &str) -> Vec<Block> {
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let resolver = UrlResolver::new("https://example.com/news/a.html", &root);
+        let body = doc.document.borrow().body();
+        BlockBuilder::new(|node| node.is_none_tag() || node.attr("class").as_deref() == Some("share"), &resolver).build(&body)
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = build(r#"<html><body><div>
+<h2> The  heading </h2>
+Loose <b>text</b>
+<p>A <em>quick</em> paragraph with <a href="/b.html">a link</a>.<script>x()</script></p>
+<div class="share">Share this</div>
+<ul><li>One</li><li>Two<br>lines</li><li> </li></ul>
+<blockquote><p>First</p><p>Second</p></blockquote>
+<table><thead><tr><th>Name</th><th>Age</th></tr></thead><tbody><tr><td>Ann</td><td>30</td></tr></tbody></table>
+</div></body></html>"#);
+        let paragraph = |text: &str, spans| Block::Paragraph { inline: Inline { text: text.to_string(), spans } };
+        assert_eq!(blocks, vec![
+            Block::Heading { level: 2, inline: Inline { text: "The heading".to_string(), spans: vec![] } },
+            paragraph("Loose text", vec![Span { kind: SpanKind::Strong, start: 6, end: 10, href: None }]),
+            paragraph("A quick paragraph with a link.", vec![
+                Span { kind: SpanKind::Emphasis, start: 2, end: 7, href: None },
+                Span { kind: SpanKind::Link, start: 23, end: 29, href: Some("https://example.com/b.html".to_string()) },
+            ]),
+            Block::List { ordered: false, items: vec![
+                Inline { text: "One".to_string(), spans: vec![] },
+                Inline { text: "Two\nlines".to_string(), spans: vec![] },
+            ] },
+            Block::Quote { inline: Inline { text: "First\nSecond".to_string(), spans: vec![] } },
+            Block::Table { rows: vec![vec!["Name".to_string(), "Age".to_string()], vec!["Ann".to_string(), "30".to_string()]] },
+        ]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let blocks = build("<html><body><h3>Title</h3><pre>a\n  b</pre></body></html>");
+        assert_eq!(serde_json::to_string(&blocks).unwrap(),
+            r#"[{"type":"heading","level":3,"text":"Title","spans":[]},{"type":"preformatted","text":"a\n  b"}]"#);
+    }
+}
@@ -0,0 +1,161 @@
+use regex::Regex;
+use render_dom::{new_handle, Handle, Node};
+
+use crate::images::image_src;
+use crate::urls::UrlResolver;
+
+
+/// Elements kept in the cleaned html. Other elements are unwrapped: their
+/// content is kept without them.
+const ALLOWED_TAGS: [&str; 44] = [
+    "p", "br", "hr", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "dl", "dt", "dd", "blockquote", "pre",
+    "code", "em", "i", "strong", "b", "u", "s", "sub", "sup", "mark", "small", "q", "cite", "abbr", "time", "a",
+    "img", "figure", "figcaption", "table", "caption", "thead", "tbody", "tfoot", "tr", "th", "td",
+];
+
+/// Elements dropped with all their content.
+const REMOVED_TAGS: [&str; 16] = [
+    "script", "style", "noscript", "template", "iframe", "object", "embed", "applet", "form", "input", "button",
+    "select", "textarea", "svg", "canvas", "nav",
+];
+
+/// Elements kept even when they end up without content.
+const VOID_TAGS: [&str; 5] = ["br", "hr", "img", "td", "th"];
+
+/// Ads and share widgets that sit inside an article body.
+const WIDGET_PATTERN: &str = r"(?i)\bshare\b|sharing|share-|social|advert|\bads?\b|-ad-|newsletter|subscribe|promo";
+
+/// Copies a content subtree into sanitized html: only allow-listed tags and
+/// attributes, urls made absolute and lazy-loaded images pointing at their
+/// real source.
+pub(crate) struct HtmlCleaner<'a, F> where F: Fn(&Node) -> bool {
+    skip: F,
+    resolver: &'a UrlResolver,
+    widget: Regex,
+}
+
+fn append(parent: &Handle, child: &Handle) {
+    parent.borrow_mut().append_child(child);
+    child.borrow_mut().set_parent(parent);
+}
+
+impl<'a, F> HtmlCleaner<'a, F> where F: Fn(&Node) -> bool {
+    pub(crate) fn new(skip: F, resolver: &'a UrlResolver) -> Self {
+        HtmlCleaner { skip, resolver, widget: Regex::new(WIDGET_PATTERN).unwrap() }
+    }
+
+    /// A detached, cleaned copy of `content`.
+    pub(crate) fn clean_tree(&self, content: &Handle) -> Handle {
+        let root = new_handle(content.borrow().shallow_clone());
+        if root.borrow().is_element_node() {
+            root.borrow_mut().mut_attrs().clear();
+        }
+        self.clean_children(content, &root);
+        root
+    }
+
+    fn clean_children(&self, from: &Handle, to: &Handle) {
+        for child in from.borrow().children() {
+            self.clean_node(&child, to);
+        }
+    }
+
+    fn is_removed(&self, node: &Node) -> bool {
+        REMOVED_TAGS.contains(&node.tag_name().as_str())
+            || (self.skip)(node)
+            || ["class", "id"].iter().filter_map(|attr| node.attr(attr)).any(|hint| self.widget.is_match(&hint))
+    }
+
+    /// Allowed attributes of an element, with urls resolved.
+    fn attributes(&self, handle: &Handle, tag: &str) -> Vec<(&'static str, String)> {
+        let node = handle.borrow();
+        let url = |attr| node.attr(attr).map(|href| self.resolver.resolve(&href)).filter(|href| {
+            let scheme = href.split(':').next().unwrap_or_default().to_lowercase();
+            !href.is_empty() && !["javascript", "vbscript", "data"].contains(&scheme.as_str())
+        });
+        let attrs: Vec<(&'static str, Option<String>)> = match tag {
+            "a" => vec![("href", url("href")), ("title", node.attr("title"))],
+            "img" => vec![
+                ("src", image_src(handle).map(|src| self.resolver.resolve(&src))),
+                ("alt", node.attr("alt")),
+                ("width", node.attr("width")),
+                ("height", node.attr("height")),
+            ],
+            "blockquote" | "q" => vec![("cite", url("cite"))],
+            "td" | "th" => vec![("colspan", node.attr("colspan")), ("rowspan", node.attr("rowspan"))],
+            "ol" => vec![("start", node.attr("start"))],
+            "time" => vec![("datetime", node.attr("datetime"))],
+            "abbr" => vec![("title", node.attr("title"))],
+            _ => vec![],
+        };
+        attrs.into_iter().filter_map(|(name, value)| Some((name, value?))).collect()
+    }
+
+    fn clean_node(&self, handle: &Handle, parent: &Handle) {
+        let node = handle.borrow();
+        if node.is_text_node() {
+            append(parent, &new_handle(node.shallow_clone()));
+            return;
+        }
+        if !node.is_element_node() || self.is_removed(&node) {
+            return;
+        }
+        let tag = node.tag_name();
+        drop(node);
+        let attrs = self.attributes(handle, &tag);
+        let has = |name| attrs.iter().any(|(attr, _)| *attr == name);
+        if tag == "img" && !has("src") {
+            return;
+        }
+        // Links that lead nowhere once scripts are gone are unwrapped as well.
+        if !ALLOWED_TAGS.contains(&tag.as_str()) || (tag == "a" && !has("href")) {
+            self.clean_children(handle, parent);
+            return;
+        }
+        let mut copy = handle.borrow().shallow_clone();
+        copy.mut_attrs().clear();
+        for (name, value) in attrs {
+            copy.set_attr(name, &value);
+        }
+        let copy = new_handle(copy);
+        self.clean_children(handle, &copy);
+        let is_empty = copy.borrow().children().all(|child| {
+            let child = child.borrow();
+            child.is_text_node() && child.text().trim().is_empty()
+        });
+        if !is_empty || VOID_TAGS.contains(&tag.as_str()) {
+            append(parent, &copy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::HtmlCleaner;
+    use crate::urls::UrlResolver;
+
+    #[test]
+    fn test_clean() {
+        let html = r#"<html><body><div class="story" onclick="track()">
+<p style="color: red">Hello <a href="/b.html" onclick="x()" target="_blank">world</a><script>alert(1)</script></p>
+<div class="share-buttons"><a href="https://twitter.com/share">Tweet</a></div>
+<section><p>Unwrapped <span class="x">span</span></p></section>
+<figure><img src="data:image/gif;base64,R0lGOD" data-src="img/a.jpg" alt="A" onerror="x()"><figcaption>Caption</figcaption></figure>
+<p><a href="javascript:void(0)">js</a></p><p> </p>
+<iframe src="https://ads.example.com"></iframe>
+</div></body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let resolver = UrlResolver::new("https://example.com/news/a.html", &root);
+        let story = doc.document.borrow().select_first(".story").unwrap().unwrap();
+        let cleaned = HtmlCleaner::new(|node| node.is_none_tag(), &resolver).clean_tree(&story);
+        assert_eq!(cleaned.borrow().inner_html().trim(), r#"<p>Hello <a href="https://example.com/b.html">world</a></p>
+
+<p>Unwrapped span</p>
+<figure><img src="https://example.com/news/img/a.jpg" alt="A"><figcaption>Caption</figcaption></figure>
+<p>js</p>"#);
+        assert_eq!(cleaned.borrow().markdown(), "Hello [world](https://example.com/b.html)\n\nUnwrapped span\n\n![A](https://example.com/news/img/a.jpg)\n\nCaption\n\njs");
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::Regex;
+use render_dom::{Handle, Node};
+
+use crate::vision::VisualSegmenter;
+
+
+/// Publisher markup that marks the article body directly; when present it wins
+/// over the scored candidates.
+const ARTICLE_BODY_SELECTOR: &str = "[class*=article-body]";
+
+/// Paragraphs shorter than this many characters do not contribute a score.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// How many ancestors of a paragraph receive a share of its score.
+const SCORE_ANCESTOR_LEVELS: usize = 5;
+
+/// Characters per element below which a candidate counts as markup-heavy.
+const MIN_TEXT_DENSITY: f64 = 25.0;
+
+/// Scores blocks of a page the way Readability does: paragraphs hand their
+/// score to their ancestors, which are then weighted by their tag, class/id
+/// hints, link density and text density.
+pub(crate) struct ContentScorer {
+    unlikely: Regex,
+    maybe: Regex,
+    positive: Regex,
+    negative: Regex,
+}
+
+fn class_and_id(node: &Node) -> String {
+    format!("{} {}", node.attr("class").unwrap_or_default(), node.attr("id").unwrap_or_default())
+}
+
+fn has_block_children(node: &Node) -> bool {
+    node.children().any(|child| {
+        let child = child.borrow();
+        child.is_element_node() && !child.is_inline_tag()
+    })
+}
+
+fn punctuation_count(text: &str) -> usize {
+    text.chars().filter(|c| matches!(c, ',' | '，' | '、' | '。' | '；' | ';')).count()
+}
+
+impl ContentScorer {
+    pub(crate) fn new() -> ContentScorer {
+        ContentScorer {
+            unlikely: Regex::new(r"(?i)-ad-|ai2html|banner|breadcrumbs|combx|comment|community|cover-wrap|disqus|extra|footer|gdpr|header|legends|menu|related|remark|replies|rss|shoutbox|sidebar|skyscraper|social|sponsor|supplemental|ad-break|agegate|pagination|pager|popup|yom-remote").unwrap(),
+            maybe: Regex::new(r"(?i)and|article|body|column|content|main|shadow").unwrap(),
+            positive: Regex::new(r"(?i)article|body|content|entry|hentry|h-entry|main|page|pagination|post|text|blog|story").unwrap(),
+            negative: Regex::new(r"(?i)-ad-|hidden|^hid$| hid$| hid |^hid |banner|combx|comment|com-|contact|foot|footer|footnote|gdpr|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|tool|widget").unwrap(),
+        }
+    }
+
+    /// Whether an element looks like navigation, comments or other page chrome
+    /// that never holds the article.
+    pub(crate) fn is_unlikely(&self, node: &Node) -> bool {
+        if !node.is_element_node() || ["body", "article", "main"].contains(&node.tag_name().as_str()) {
+            return false;
+        }
+        let hints = class_and_id(node);
+        self.unlikely.is_match(&hints) && !self.maybe.is_match(&hints)
+    }
+
+    /// Visible text of a node, leaving out scripts, styles and unlikely blocks.
+    fn inner_text(&self, node: &Node) -> String {
+        node.descendants_text_skip(|node| {
+            node.is_element_node() && (node.is_none_tag() || self.is_unlikely(node))
+        }).trim().to_string()
+    }
+
+    fn class_weight(&self, node: &Node) -> f64 {
+        let mut weight = 0.0;
+        for hint in [node.attr("class"), node.attr("id")].into_iter().flatten() {
+            if self.negative.is_match(&hint) {
+                weight -= 25.0;
+            }
+            if self.positive.is_match(&hint) {
+                weight += 25.0;
+            }
+        }
+        weight
+    }
+
+    fn initial_score(&self, node: &Node) -> f64 {
+        let tag_score = match node.tag_name().as_str() {
+            "article" => 10.0,
+            "div" | "section" | "main" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+        tag_score + self.class_weight(node)
+    }
+
+    fn link_density(&self, node: &Node, text_length: usize) -> f64 {
+        if text_length == 0 {
+            return 0.0;
+        }
+        let link_length = node.descendants()
+            .filter(|child| {
+                let child = child.borrow();
+                child.is_element_node() && child.tag_name() == "a"
+            })
+            .map(|link| self.inner_text(&link.borrow()).chars().count())
+            .sum::<usize>();
+        (link_length as f64 / text_length as f64).min(1.0)
+    }
+
+    fn text_density(&self, node: &Node, text_length: usize) -> f64 {
+        let elements = node.descendants().filter(|child| child.borrow().is_element_node()).count();
+        text_length as f64 / (elements + 1) as f64
+    }
+
+    fn is_paragraph(&self, node: &Node) -> bool {
+        match node.tag_name().as_str() {
+            "p" | "pre" | "td" => true,
+            "div" | "section" => !has_block_children(node),
+            _ => false,
+        }
+    }
+
+    /// Returns the highest scoring block under `body`, if any paragraph on the
+    /// page is long enough to be scored.
+    pub(crate) fn best_candidate(&self, body: &Handle) -> Option<Handle> {
+        let body_ptr = Rc::as_ptr(body);
+        let mut candidates: Vec<Handle> = vec![];
+        let mut scores: HashMap<*const _, f64> = HashMap::new();
+        let paragraphs = body.borrow().descendants_skip(|node| {
+            node.is_element_node() && (node.is_none_tag() || self.is_unlikely(node))
+        }).filter(|node| {
+            let node = node.borrow();
+            node.is_element_node() && self.is_paragraph(&node)
+        }).collect::<Vec<_>>();
+
+        for paragraph in paragraphs {
+            let text = self.inner_text(&paragraph.borrow());
+            let length = text.chars().count();
+            if length < MIN_PARAGRAPH_LENGTH {
+                continue;
+            }
+            let score = 1.0 + punctuation_count(&text) as f64 + (length as f64 / 100.0).min(3.0);
+            let mut ancestor = paragraph.borrow().parent();
+            for level in 0..SCORE_ANCESTOR_LEVELS {
+                let node = match ancestor {
+                    Some(node) if node.borrow().is_element_node() => node,
+                    _ => break,
+                };
+                let ptr = Rc::as_ptr(&node);
+                let divider = match level {
+                    0 => 1.0,
+                    1 => 2.0,
+                    _ => level as f64 * 3.0,
+                };
+                *scores.entry(ptr).or_insert_with(|| {
+                    candidates.push(node.clone());
+                    self.initial_score(&node.borrow())
+                }) += score / divider;
+                if ptr == body_ptr {
+                    break;
+                }
+                ancestor = node.borrow().parent();
+            }
+        }
+
+        candidates.into_iter()
+            .map(|candidate| {
+                let node = candidate.borrow();
+                let length = self.inner_text(&node).chars().count();
+                let density = (self.text_density(&node, length) / MIN_TEXT_DENSITY).clamp(0.25, 1.0);
+                let score = scores[&Rc::as_ptr(&candidate)] * (1.0 - self.link_density(&node, length)) * density;
+                drop(node);
+                (candidate, score)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(candidate, _)| candidate)
+    }
+}
+
+/// Picks the node holding the article body: the publisher's `article-body`
+/// block when present, otherwise the main region of the rendered layout when
+/// the page carries render geometry, otherwise the best scored block, otherwise
+/// `body`.
+pub(crate) fn find_content_node(body: &Handle, scorer: &ContentScorer, segmenter: Option<&VisualSegmenter>) -> Handle {
+    if let Some(node) = body.borrow().select_first(ARTICLE_BODY_SELECTOR).ok().flatten() {
+        return node;
+    }
+    segmenter.and_then(|segmenter| segmenter.main_region(body))
+        .or_else(|| scorer.best_candidate(body))
+        .unwrap_or_else(|| body.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::{ContentScorer, find_content_node};
+
+    const HTML: &str = r#"<html><head><title>t</title></head><body>
+<div id="nav" class="menu"><a href="/">Home</a> <a href="/world">World news, politics, and more</a></div>
+<div class="story-content">
+  <p>The first paragraph of the story is long enough, with commas, to be scored as content.</p>
+  <p>The second paragraph continues the story, adding details, quotes, and more context here.</p>
+</div>
+<div class="footer"><p>Copyright notice, all rights reserved, do not copy this page anywhere.</p></div>
+</body></html>"#;
+
+    #[test]
+    fn test_find_content_node() {
+        let doc = parse_document(&mut HTML.as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        let node = find_content_node(&body, &ContentScorer::new(), None);
+        assert_eq!(node.borrow().attr("class"), Some("story-content".to_string()));
+
+        let doc = parse_document(&mut HTML.replace("story-content", "x article-body").as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        let node = find_content_node(&body, &ContentScorer::new(), None);
+        assert_eq!(node.borrow().attr("class"), Some("x article-body".to_string()));
+    }
+
+    #[test]
+    fn test_unlikely_candidates() {
+        let doc = parse_document(&mut HTML.as_bytes()).unwrap();
+        let scorer = ContentScorer::new();
+        let document = doc.document.borrow();
+        let footer = document.select_first(".footer").unwrap().unwrap();
+        let story = document.select_first(".story-content").unwrap().unwrap();
+        assert!(scorer.is_unlikely(&footer.borrow()));
+        assert!(!scorer.is_unlikely(&story.borrow()));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use render_dom::DomError;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input contained no HTML.
+    EmptyHtml,
+    /// The HTML could not be read or parsed into a document.
+    Parse(DomError),
+    /// The document has no root element.
+    MissingRoot,
+    MissingTitle,
+    MissingContent,
+    MissingFeature,
+}
+
+impl Error {
+    /// A stable machine-readable name for the error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::EmptyHtml => "empty_html",
+            Error::Parse(_) => "parse_error",
+            Error::MissingRoot => "missing_root",
+            Error::MissingTitle => "missing_title",
+            Error::MissingContent => "missing_content",
+            Error::MissingFeature => "missing_feature",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyHtml => write!(f, "html is empty"),
+            Error::Parse(err) => write!(f, "parse html failed: {}", err),
+            Error::MissingRoot => write!(f, "not found root element"),
+            Error::MissingTitle => write!(f, "not found title"),
+            Error::MissingContent => write!(f, "not found content"),
+            Error::MissingFeature => write!(f, "not found feature"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DomError> for Error {
+    fn from(err: DomError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+/// Errors from loading and validating site rules.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    /// A rule file could not be read.
+    Io { path: String, message: String },
+    /// A rule file is not valid TOML or JSON, or has an unknown extension.
+    Parse { path: String, message: String },
+    /// A rule has an invalid selector or pattern, or matches no site.
+    Invalid { rule: String, message: String },
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::Io { path, message } => write!(f, "read rules {} failed: {}", path, message),
+            RuleError::Parse { path, message } => write!(f, "parse rules {} failed: {}", path, message),
+            RuleError::Invalid { rule, message } => write!(f, "invalid rule {}: {}", rule, message),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Errors from reading a WARC archive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarcError {
+    /// The archive could not be read or decompressed.
+    Io(String),
+    /// A record is not valid WARC.
+    Format(String),
+}
+
+impl fmt::Display for WarcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarcError::Io(msg) => write!(f, "read warc failed: {}", msg),
+            WarcError::Format(msg) => write!(f, "invalid warc record: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WarcError {}
+
+impl From<std::io::Error> for WarcError {
+    fn from(err: std::io::Error) -> Self {
+        WarcError::Io(err.to_string())
+    }
+}
+
+/// Why a WARC record gave no feature: its HTTP message is malformed, or no
+/// article could be extracted from it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarcItemError {
+    Response(WarcError),
+    Extract(Error),
+}
+
+impl WarcItemError {
+    /// A stable machine-readable name for the error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WarcItemError::Response(_) => "invalid_response",
+            WarcItemError::Extract(err) => err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for WarcItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarcItemError::Response(WarcError::Io(msg) | WarcError::Format(msg)) => write!(f, "invalid http response: {}", msg),
+            WarcItemError::Extract(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for WarcItemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WarcItemError::Response(err) => Some(err),
+            WarcItemError::Extract(err) => Some(err),
+        }
+    }
+}
+
+impl From<Error> for WarcItemError {
+    fn from(err: Error) -> Self {
+        WarcItemError::Extract(err)
+    }
+}
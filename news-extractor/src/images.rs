@@ -0,0 +1,242 @@
+use std::rc::Rc;
+
+use regex::Regex;
+use render_dom::{Handle, Node};
+use serde::{Serialize, Deserialize};
+
+use crate::parser::normalize;
+use crate::vision::VisualSegmenter;
+
+
+/// Attributes lazy-loading scripts keep the real image url in, most specific first.
+const LAZY_SRC_ATTRS: [&str; 6] = ["data-src", "data-original", "data-lazy-src", "data-url", "data-hi-res-src", "data-lazy"];
+
+const SRCSET_ATTRS: [&str; 3] = ["srcset", "data-srcset", "data-lazy-srcset"];
+
+/// Images smaller than this on either side are icons, bullets or pixels.
+const MIN_IMAGE_SIZE: i32 = 100;
+
+/// Banners wider or taller than this ratio are not article images.
+const MAX_ASPECT_RATIO: f64 = 4.0;
+
+/// IAB ad unit sizes.
+const AD_SIZES: [(i32, i32); 8] = [(300, 250), (336, 280), (728, 90), (970, 90), (970, 250), (160, 600), (300, 600), (320, 50)];
+
+/// How many levels up from an `<img>` a `<figure>` is looked for.
+const FIGURE_LEVELS: usize = 4;
+
+/// An image of the article. `width` and `height` are the rendered size, or the
+/// size declared in the markup when the page was not rendered, and `-1` when
+/// unknown.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Image {
+    pub src: String,
+    pub alt: String,
+    pub caption: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+fn is_placeholder(src: &str) -> bool {
+    src.is_empty() || src.starts_with("data:") || src.starts_with("about:")
+}
+
+/// The candidates of a `srcset` with their descriptors. As in the html spec, a
+/// url runs to the next whitespace, so commas inside it (as in `data:` urls)
+/// are kept, and its descriptors run to the next comma.
+fn srcset_candidates(srcset: &str) -> Vec<(&str, &str)> {
+    let mut candidates = vec![];
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+        let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = &rest[..url_end];
+        if url.ends_with(',') {
+            candidates.push((url.trim_end_matches(','), ""));
+            rest = &rest[url_end..];
+            continue;
+        }
+        let descriptors_end = rest[url_end..].find(',').map_or(rest.len(), |i| url_end + i);
+        candidates.push((url, rest[url_end..descriptors_end].trim()));
+        rest = &rest[descriptors_end..];
+    }
+}
+
+/// The largest candidate of a `srcset`, by width descriptor or density.
+fn best_srcset_candidate(srcset: &str) -> Option<String> {
+    srcset_candidates(srcset).into_iter()
+        .map(|(url, descriptors)| {
+            let size = descriptors.split_whitespace().next().and_then(|descriptor| {
+                let (value, unit) = descriptor.split_at(descriptor.len().saturating_sub(1));
+                let value = value.parse::<f64>().ok()?;
+                // Rank densities below widths: "2x" of an unknown width vs "800w".
+                Some(if unit == "w" { value } else { value / 1000.0 })
+            }).unwrap_or(0.001);
+            (url.to_string(), size)
+        })
+        .filter(|(url, _)| !is_placeholder(url))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(url, _)| url)
+}
+
+fn srcset_of(node: &Node) -> Option<String> {
+    SRCSET_ATTRS.iter().filter_map(|attr| node.attr(attr)).find_map(|srcset| best_srcset_candidate(&srcset))
+}
+
+/// The real url of an `<img>`: the largest `<picture><source>` or `srcset`
+/// candidate, then lazy-load attributes, then `src`.
+pub(crate) fn image_src(handle: &Handle) -> Option<String> {
+    let img = handle.borrow();
+    let picture_source = img.parent()
+        .filter(|parent| parent.borrow().tag_name() == "picture")
+        .and_then(|picture| picture.borrow().children()
+            .filter(|child| child.borrow().is_element_node() && child.borrow().tag_name() == "source")
+            .find_map(|source| srcset_of(&source.borrow())));
+    picture_source
+        .or_else(|| srcset_of(&img))
+        .or_else(|| LAZY_SRC_ATTRS.iter().filter_map(|attr| img.attr(attr)).map(|s| s.trim().to_string()).find(|s| !is_placeholder(s)))
+        .or_else(|| img.attr("src").map(|s| s.trim().to_string()).filter(|s| !is_placeholder(s)))
+}
+
+fn find_caption(img: &Handle) -> String {
+    let mut ancestor = img.borrow().parent();
+    for _ in 0..FIGURE_LEVELS {
+        let node = match ancestor {
+            Some(node) if node.borrow().is_element_node() => node,
+            _ => break,
+        };
+        if node.borrow().tag_name() == "figure" {
+            return node.borrow().select_first("figcaption").ok().flatten()
+                .map(|caption| normalize(&caption.borrow().descendants_text_skip(|node| node.is_element_node() && node.is_none_tag())))
+                .unwrap_or_default();
+        }
+        ancestor = node.borrow().parent();
+    }
+    String::new()
+}
+
+fn is_within(node: &Handle, ancestor: &Handle) -> bool {
+    let mut current = Some(node.clone());
+    while let Some(node) = current {
+        if Rc::ptr_eq(&node, ancestor) {
+            return true;
+        }
+        current = node.borrow().parent();
+    }
+    false
+}
+
+fn is_ad_size(width: i32, height: i32) -> bool {
+    AD_SIZES.contains(&(width, height))
+}
+
+struct Candidate {
+    image: Image,
+    score: f64,
+}
+
+/// Collects the images of a page, dropping icons, tracking pixels, ads and
+/// hidden images, and ranks them by size, by whether they sit in the article
+/// `content` and have a caption, and by how high on the page they are.
+pub(crate) fn find_images(body: &Handle, content: &Handle, segmenter: Option<&VisualSegmenter>) -> Vec<Image> {
+    let rubbish = Regex::new(r"(?i)\b(ad|ads|advert|avatar|badge|banner|button|emoji|icon|logo|pixel|spacer|sprite|tracking|1x1)\b|doubleclick|/ads?/").unwrap();
+    let images = body.borrow().descendants_skip(|node| {
+        node.is_element_node() && (node.is_none_tag() || segmenter.is_some_and(|segmenter| segmenter.is_hidden(node)))
+    }).filter(|node| {
+        let node = node.borrow();
+        node.is_element_node() && node.tag_name() == "img"
+    }).collect::<Vec<_>>();
+
+    let mut candidates: Vec<Candidate> = vec![];
+    for handle in images {
+        let src = match image_src(&handle) {
+            Some(src) => src,
+            None => continue,
+        };
+        let img = handle.borrow();
+        let hints = format!("{} {} {}", src, img.attr("class").unwrap_or_default(), img.attr("id").unwrap_or_default());
+        if rubbish.is_match(&hints) || candidates.iter().any(|candidate| candidate.image.src == src) {
+            continue;
+        }
+        let vision = &img.custom_node_data().vision;
+        let (width, height, ypos) = if segmenter.is_some() && vision.width > 0 && vision.height > 0 {
+            (vision.width, vision.height, vision.ypos.max(0))
+        } else {
+            let size = |attr| img.attr(attr).and_then(|s| s.trim().trim_end_matches("px").parse::<i32>().ok()).unwrap_or(-1);
+            (size("width"), size("height"), -1)
+        };
+        if (0..MIN_IMAGE_SIZE).contains(&width) || (0..MIN_IMAGE_SIZE).contains(&height) || is_ad_size(width, height) {
+            continue;
+        }
+        if width > 0 && height > 0 && (width as f64 / height as f64).max(height as f64 / width as f64) > MAX_ASPECT_RATIO {
+            continue;
+        }
+
+        let caption = find_caption(&handle);
+        let mut score = if width > 0 && height > 0 { (width as f64 * height as f64).sqrt() } else { MIN_IMAGE_SIZE as f64 };
+        if is_within(&handle, content) {
+            score *= 2.0;
+        }
+        if !caption.is_empty() {
+            score *= 1.2;
+        }
+        if ypos >= 0 {
+            score /= 1.0 + ypos as f64 / 2000.0;
+        }
+        let image = Image {
+            src,
+            alt: normalize(&img.attr("alt").unwrap_or_default()),
+            caption,
+            width,
+            height,
+        };
+        candidates.push(Candidate { image, score });
+    }
+    // A stable sort keeps document order among images without size hints.
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates.into_iter().map(|candidate| candidate.image).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::{best_srcset_candidate, find_images};
+
+    #[test]
+    fn test_srcset() {
+        assert_eq!(best_srcset_candidate("a.jpg 320w, b.jpg 1024w, c.jpg 640w"), Some("b.jpg".to_string()));
+        assert_eq!(best_srcset_candidate("a.jpg, b.jpg 2x"), Some("b.jpg".to_string()));
+        assert_eq!(best_srcset_candidate("data:image/gif;base64,R0lGOD 1x"), None);
+        assert_eq!(best_srcset_candidate("a.jpg 1x,b.jpg 2x"), Some("b.jpg".to_string()));
+        assert_eq!(best_srcset_candidate("a.jpg 640w,b.jpg 1280w ,c.jpg,"), Some("b.jpg".to_string()));
+        assert_eq!(best_srcset_candidate("data:image/gif;base64,R0lGOD 2x, a.jpg 1x"), Some("a.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_find_images() {
+        let html = r#"<html><body>
+<div class="header"><img src="/logo.png" alt="Site"></div>
+<img src="/pixel.gif" width="1" height="1">
+<div class="story">
+  <p>Text</p>
+  <figure><picture><source srcset="/lead-640.webp 640w, /lead-1280.webp 1280w"><img src="/lead.jpg" alt=" Lead  image "></picture>
+  <figcaption>The <b>caption</b></figcaption></figure>
+  <img src="data:image/gif;base64,R0lGOD" data-src="/lazy.jpg" width="800" height="450">
+  <img src="/ad.jpg" width="300" height="250">
+</div>
+</body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let document = doc.document.borrow();
+        let body = document.body();
+        let content = document.select_first(".story").unwrap().unwrap();
+        let images = find_images(&body, &content, None);
+        assert_eq!(images.iter().map(|image| image.src.as_str()).collect::<Vec<_>>(), vec!["/lazy.jpg", "/lead-1280.webp"]);
+        assert_eq!(images[1].alt, "Lead image");
+        assert_eq!(images[1].caption, "The caption");
+        assert_eq!((images[0].width, images[0].height), (800, 450));
+    }
+}
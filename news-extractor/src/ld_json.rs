@@ -0,0 +1,349 @@
+//! Typed access to the JSON-LD (`application/ld+json`) blocks of a page.
+//!
+//! Every block in the document is gathered, top-level arrays and `@graph`s are
+//! flattened into one list of entities, and `{"@id": ...}` references are
+//! resolved against the entities defining them.
+
+use std::collections::HashMap;
+
+use render_dom::Handle;
+use serde_json::{Map, Value};
+
+use crate::parser::normalize;
+
+
+const LD_JSON_SELECTOR: &str = r#"script[type="application/ld+json" i]"#;
+
+/// `@type`s describing an article, preferred over the page types below.
+const ARTICLE_TYPES: [&str; 12] = ["Article", "NewsArticle", "ReportageNewsArticle", "AnalysisNewsArticle",
+    "OpinionNewsArticle", "BackgroundNewsArticle", "ReviewNewsArticle", "BlogPosting", "LiveBlogPosting", "Report",
+    "ScholarlyArticle", "TechArticle"];
+
+const PAGE_TYPES: [&str; 3] = ["WebPage", "ItemPage", "CollectionPage"];
+
+const ORGANIZATION_TYPES: [&str; 3] = ["Organization", "NewsMediaOrganization", "Corporation"];
+
+/// An `Article` or one of its subtypes, or the `WebPage` when a page has no
+/// article entity.
+#[derive(Debug, Clone, Default)]
+pub struct Article {
+    /// The `@type` of the entity, e.g. `NewsArticle`.
+    pub kind: String,
+    pub headline: String,
+    pub description: String,
+    pub url: String,
+    pub authors: Vec<String>,
+    pub date_published: String,
+    pub date_modified: String,
+    pub section: String,
+    pub keywords: Vec<String>,
+    pub language: String,
+    pub publisher: Option<Organization>,
+    pub images: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Organization {
+    pub name: String,
+    pub url: String,
+    pub logo: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Breadcrumb {
+    pub position: i64,
+    pub name: String,
+    pub url: String,
+}
+
+/// Items of a `BreadcrumbList`, ordered by their `position`.
+#[derive(Debug, Clone, Default)]
+pub struct BreadcrumbList {
+    pub items: Vec<Breadcrumb>,
+}
+
+/// All JSON-LD entities of a document.
+#[derive(Debug, Default)]
+pub struct LdJson {
+    entities: Vec<Value>,
+    ids: HashMap<String, Value>,
+}
+
+/// Parses a script body, tolerating the HTML comment and CDATA wrappers some
+/// CMSs put around it.
+fn parse_block(text: &str) -> Option<Value> {
+    let text = text.trim();
+    serde_json::from_str(text).ok().or_else(|| {
+        let text = text.trim_start_matches("<!--").trim_end_matches("-->").trim()
+            .trim_start_matches("//<![CDATA[").trim_end_matches("//]]>").trim();
+        serde_json::from_str(text).ok()
+    })
+}
+
+fn flatten(value: Value, entities: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => items.into_iter().for_each(|item| flatten(item, entities)),
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                flatten(graph, entities);
+            }
+            if object.keys().any(|key| key != "@context") {
+                entities.push(Value::Object(object));
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Registers every object carrying an `@id` and more than a reference, nested
+/// ones included; the first definition of an id wins.
+fn collect_ids(value: &Value, ids: &mut HashMap<String, Value>) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_ids(item, ids)),
+        Value::Object(object) => {
+            if let Some(id) = object.get("@id").and_then(Value::as_str) {
+                if object.len() > 1 && !ids.contains_key(id) {
+                    ids.insert(id.to_string(), value.clone());
+                }
+            }
+            object.values().for_each(|child| collect_ids(child, ids));
+        },
+        _ => {},
+    }
+}
+
+fn is_reference(object: &Map<String, Value>) -> bool {
+    object.contains_key("@id") && object.keys().all(|key| key == "@id" || key == "@type")
+}
+
+/// Local name of a type such as `http://schema.org/NewsArticle` or `schema:NewsArticle`.
+fn type_name(t: &str) -> &str {
+    t.rsplit(['/', ':']).next().unwrap_or(t)
+}
+
+fn types(value: &Value) -> Vec<&str> {
+    match value.get("@type") {
+        Some(Value::String(t)) => vec![type_name(t)],
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).map(type_name).collect(),
+        _ => vec![],
+    }
+}
+
+fn has_type(value: &Value, kinds: &[&str]) -> bool {
+    types(value).iter().any(|t| kinds.contains(t))
+}
+
+impl LdJson {
+    /// Gathers the JSON-LD blocks anywhere under `root`. Blocks that are not
+    /// valid JSON are skipped.
+    pub fn new(root: &Handle) -> LdJson {
+        let scripts = root.borrow().select(LD_JSON_SELECTOR).unwrap_or_default();
+        let mut entities = vec![];
+        for value in scripts.iter().filter_map(|script| parse_block(&script.borrow().children_text())) {
+            flatten(value, &mut entities);
+        }
+        let mut ids = HashMap::new();
+        entities.iter().for_each(|entity| collect_ids(entity, &mut ids));
+        LdJson { entities, ids }
+    }
+
+    pub fn entities(&self) -> &[Value] {
+        &self.entities
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Follows an `{"@id": ...}` reference to the entity it names; other
+    /// values, and references to unknown ids, are returned unchanged.
+    pub fn resolve<'a>(&'a self, value: &'a Value) -> &'a Value {
+        match value {
+            Value::Object(object) if is_reference(object) => object.get("@id")
+                .and_then(Value::as_str)
+                .and_then(|id| self.ids.get(id))
+                .unwrap_or(value),
+            _ => value,
+        }
+    }
+
+    /// First entity having one of the given `@type`s.
+    pub fn find(&self, kinds: &[&str]) -> Option<&Value> {
+        self.entities.iter().find(|entity| has_type(entity, kinds))
+    }
+
+    /// Values of `key`, with lists flattened and references resolved.
+    fn values<'a>(&'a self, value: &'a Value, key: &str) -> Vec<&'a Value> {
+        match value.get(key).map(|value| self.resolve(value)) {
+            Some(Value::Array(items)) => items.iter().map(|item| self.resolve(item)).collect(),
+            Some(Value::Null) | None => vec![],
+            Some(value) => vec![value],
+        }
+    }
+
+    /// Texts of `key`: strings as is, objects by their `name`.
+    fn names(&self, value: &Value, key: &str) -> Vec<String> {
+        self.values(value, key).into_iter()
+            .filter_map(|value| match value {
+                Value::String(s) => Some(normalize(s)),
+                Value::Object(_) => value.get("name").and_then(Value::as_str).map(normalize),
+                _ => None,
+            })
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    fn name(&self, value: &Value, key: &str) -> String {
+        self.names(value, key).into_iter().next().unwrap_or_default()
+    }
+
+    /// Urls of an image property, which may be a url, an `ImageObject` or a
+    /// list of either.
+    fn urls(&self, value: &Value, key: &str) -> Vec<String> {
+        self.values(value, key).into_iter()
+            .filter_map(|value| match value {
+                Value::String(s) => Some(s.trim().to_string()),
+                Value::Object(object) => ["url", "contentUrl", "@id"].iter()
+                    .find_map(|key| object.get(*key).and_then(Value::as_str))
+                    .map(|s| s.trim().to_string()),
+                _ => None,
+            })
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    fn organization_of(&self, value: &Value) -> Option<Organization> {
+        let organization = match value {
+            Value::String(name) => Organization { name: normalize(name), ..Default::default() },
+            Value::Object(_) => Organization {
+                name: self.name(value, "name"),
+                url: self.urls(value, "url").into_iter().next().unwrap_or_default(),
+                logo: self.urls(value, "logo").into_iter().next(),
+            },
+            _ => return None,
+        };
+        Some(organization).filter(|organization| !organization.name.is_empty())
+    }
+
+    /// The article the page is about, or its `WebPage` entity when there is
+    /// no article.
+    pub fn article(&self) -> Option<Article> {
+        let entity = self.find(&ARTICLE_TYPES).or_else(|| self.find(&PAGE_TYPES))?;
+        let headline = self.name(entity, "headline");
+        Some(Article {
+            kind: types(entity).into_iter().next().unwrap_or_default().to_string(),
+            headline: if headline.is_empty() { self.name(entity, "name") } else { headline },
+            description: self.name(entity, "description"),
+            url: self.urls(entity, "url").into_iter().next().unwrap_or_default(),
+            authors: self.names(entity, "author"),
+            date_published: self.name(entity, "datePublished"),
+            date_modified: self.name(entity, "dateModified"),
+            section: self.name(entity, "articleSection"),
+            keywords: self.names(entity, "keywords").iter()
+                .flat_map(|keywords| keywords.split(','))
+                .map(normalize)
+                .filter(|keyword| !keyword.is_empty())
+                .collect(),
+            language: self.name(entity, "inLanguage"),
+            publisher: self.values(entity, "publisher").into_iter().find_map(|value| self.organization_of(value)),
+            images: [self.urls(entity, "image"), self.urls(entity, "thumbnailUrl")].concat(),
+        })
+    }
+
+    /// The first `Organization` entity, typically the publisher of the site.
+    pub fn organization(&self) -> Option<Organization> {
+        self.organization_of(self.find(&ORGANIZATION_TYPES)?)
+    }
+
+    pub fn breadcrumbs(&self) -> Option<BreadcrumbList> {
+        let list = self.find(&["BreadcrumbList"])?;
+        let mut items = self.values(list, "itemListElement").into_iter()
+            .map(|element| {
+                let item = element.get("item").map(|item| self.resolve(item));
+                let mut name = self.name(element, "name");
+                if name.is_empty() {
+                    name = item.map(|item| self.name(item, "name")).unwrap_or_default();
+                }
+                let url = match item {
+                    Some(Value::String(url)) => url.trim().to_string(),
+                    Some(item) => self.urls(item, "@id").into_iter().chain(self.urls(item, "url")).next().unwrap_or_default(),
+                    None => String::new(),
+                };
+                Breadcrumb { position: element.get("position").and_then(Value::as_i64).unwrap_or_default(), name, url }
+            })
+            .filter(|breadcrumb| !breadcrumb.name.is_empty())
+            .collect::<Vec<_>>();
+        items.sort_by_key(|breadcrumb| breadcrumb.position);
+        Some(BreadcrumbList { items })
+    }
+
+    /// Image urls of the page: the article's images first, then those of any
+    /// other entity.
+    pub fn images(&self) -> Vec<String> {
+        let mut images = self.article().map(|article| article.images).unwrap_or_default();
+        for url in self.entities.iter().flat_map(|entity| self.urls(entity, "image")) {
+            if !images.contains(&url) {
+                images.push(url);
+            }
+        }
+        images
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::LdJson;
+
+    #[test]
+    fn test_graph_and_references() {
+        let html = r#"<html><head><script type="application/ld+json">{"@context": "https://schema.org", "@graph": [
+  {"@type": "WebPage", "@id": "https://example.com/a#page", "name": "Page"},
+  {"@type": ["NewsArticle"], "headline": "Big news", "author": {"@id": "https://example.com/#jane"},
+   "publisher": {"@id": "https://example.com/#org"}, "image": {"@id": "https://example.com/a#image"},
+   "keywords": ["economy", "markets, stocks"], "datePublished": "2022-03-01"},
+  {"@type": "Person", "@id": "https://example.com/#jane", "name": "Jane Doe"},
+  {"@type": "Organization", "@id": "https://example.com/#org", "name": "Daily News", "logo": {"@type": "ImageObject", "url": "https://example.com/logo.png"}},
+  {"@type": "ImageObject", "@id": "https://example.com/a#image", "contentUrl": "https://example.com/a.jpg"}
+]}</script></head><body>
+<script type="application/LD+JSON">[{"@type": "BreadcrumbList", "itemListElement": [
+  {"@type": "ListItem", "position": 2, "item": {"@id": "https://example.com/business", "name": "Business"}},
+  {"@type": "ListItem", "position": 1, "name": "Home", "item": "https://example.com/"}]}]</script>
+<script type="application/ld+json">{not json</script>
+</body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let ld_json = LdJson::new(&doc.document.borrow().root());
+        assert_eq!(ld_json.entities().len(), 6);
+
+        let article = ld_json.article().unwrap();
+        assert_eq!(article.kind, "NewsArticle");
+        assert_eq!(article.headline, "Big news");
+        assert_eq!(article.authors, vec!["Jane Doe"]);
+        assert_eq!(article.keywords, vec!["economy", "markets", "stocks"]);
+        assert_eq!(article.images, vec!["https://example.com/a.jpg"]);
+        let publisher = article.publisher.unwrap();
+        assert_eq!(publisher.name, "Daily News");
+        assert_eq!(publisher.logo.as_deref(), Some("https://example.com/logo.png"));
+        assert_eq!(ld_json.organization().unwrap().name, "Daily News");
+
+        let breadcrumbs = ld_json.breadcrumbs().unwrap().items;
+        assert_eq!(breadcrumbs.iter().map(|item| item.name.as_str()).collect::<Vec<_>>(), vec!["Home", "Business"]);
+        assert_eq!(breadcrumbs[1].url, "https://example.com/business");
+    }
+
+    #[test]
+    fn test_image_shapes() {
+        for (image, expected) in [
+            (r#""https://example.com/a.jpg""#, vec!["https://example.com/a.jpg"]),
+            (r#"["https://example.com/a.jpg", {"url": "https://example.com/b.jpg"}]"#, vec!["https://example.com/a.jpg", "https://example.com/b.jpg"]),
+            (r#"{"@type": "ImageObject", "url": "https://example.com/a.jpg"}"#, vec!["https://example.com/a.jpg"]),
+        ] {
+            let html = format!(r#"<html><head><script type="application/ld+json">{{"@type": "Thing", "image": {image}}}</script></head><body></body></html>"#);
+            let doc = parse_document(&mut html.as_bytes()).unwrap();
+            let ld_json = LdJson::new(&doc.document.borrow().root());
+            assert!(ld_json.article().is_none());
+            assert_eq!(ld_json.images(), expected);
+        }
+    }
+}
@@ -1,14 +1,77 @@
 use std::io;
 
-use render_dom::parse_document;
+use render_dom::{parse_document_with_charset, DomError};
 
+mod article;
+mod blocks;
+mod clean;
+mod content;
+mod error;
+mod images;
+pub mod ld_json;
+pub mod metadata;
 mod parser;
+mod rules;
+mod title;
+mod urls;
+mod vision;
+mod warc;
+pub use blocks::{Block, Inline, Span, SpanKind};
+pub use error::{Error, RuleError, WarcError, WarcItemError};
+pub use images::Image;
 pub use parser::Feature;
+pub use rules::{CompiledRule, Replace, SiteRule, SiteRules};
+pub use warc::{parse_http_response, parse_warc, HttpResponse, WarcFeature, WarcParser, WarcReader, WarcRecord};
 
 
-pub fn parse_html<R>(url: String, r: &mut R) -> Result<Feature, String> 
+pub fn parse_html<R>(url: String, r: &mut R) -> Result<Feature, Error> 
  where R: io::Read {
-    let doc = parse_document(r)?;
+    parse_html_with_charset(url, r, None)
+}
+
+/// Like `parse_html`, with the `Content-Type` the page was served with used as
+/// a hint for its character encoding.
+pub fn parse_html_with_charset<R>(url: String, r: &mut R, content_type: Option<&str>) -> Result<Feature, Error>
+ where R: io::Read {
+    parse_html_with_rules(url, r, content_type, &SiteRules::default())
+}
+
+/// The absolute url a saved page declares for itself: `<link rel="canonical">`,
+/// then `og:url`.
+pub fn find_page_url(html: &[u8]) -> Option<String> {
+    let doc = parse_document_with_charset(&mut &html[..], None).ok()?;
+    let borrow = doc.document.borrow();
+    let root = borrow.try_root()?;
+    let url = metadata::Metadata::new(&root).canonical_url()?.trim().to_string();
+    url::Url::parse(&url).ok().filter(|url| url.has_host()).map(|url| url.to_string())
+}
+
+/// Like `parse_html_with_charset`, with the first of `rules` matching `url`
+/// applied on top of the built-in heuristics.
+pub fn parse_html_with_rules<R>(url: String, r: &mut R, content_type: Option<&str>, rules: &SiteRules) -> Result<Feature, Error>
+ where R: io::Read {
+    let mut html = vec![];
+    r.read_to_end(&mut html).map_err(|e| DomError::Io(e.to_string()))?;
+    if html.iter().all(|b| b.is_ascii_whitespace()) {
+        return Err(Error::EmptyHtml);
+    }
+    let doc = parse_document_with_charset(&mut html.as_slice(), content_type)?;
     let borrow = doc.document.borrow();
-    parser::parse_tree(url, borrow.root())
+    let root = borrow.try_root().ok_or(Error::MissingRoot)?;
+    parser::parse_tree(url, root, rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_page_url;
+
+    #[test]
+    fn test_find_page_url() {
+        assert_eq!(find_page_url(br#"<html><head><link rel="canonical" href=" https://example.com/a "></head></html>"#),
+            Some("https://example.com/a".to_string()));
+        assert_eq!(find_page_url(br#"<html><head><meta property="og:url" content="https://example.com/og"></head></html>"#),
+            Some("https://example.com/og".to_string()));
+        assert_eq!(find_page_url(br#"<html><head><link rel="canonical" href="/relative"></head></html>"#), None);
+        assert_eq!(find_page_url(b"<html><body>no head</body></html>"), None);
+    }
 }
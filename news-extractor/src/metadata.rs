@@ -0,0 +1,202 @@
+//! Page metadata from `<meta>` and `<link>` tags: OpenGraph, Twitter Cards,
+//! the meta description and the canonical, AMP and favicon links.
+
+use std::collections::HashMap;
+
+use render_dom::Handle;
+
+use crate::parser::normalize;
+
+
+/// `<meta>` values keyed by their lowercased `name`, `property`, `itemprop` or
+/// `http-equiv`, in document order.
+#[derive(Debug, Default)]
+pub(crate) struct MetaTags(HashMap<String, Vec<String>>);
+
+impl MetaTags {
+    pub(crate) fn new(root: &Handle) -> MetaTags {
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for meta in root.borrow().descendants().filter(|node| {
+            let node = node.borrow();
+            node.is_element_node() && node.tag_name() == "meta"
+        }) {
+            let meta = meta.borrow();
+            let content = match meta.attr("content").map(|s| normalize(&s)) {
+                Some(content) if !content.is_empty() => content,
+                _ => continue,
+            };
+            for key in ["name", "property", "itemprop", "http-equiv"].iter().filter_map(|attr| meta.attr(attr)) {
+                tags.entry(key.trim().to_lowercase()).or_default().push(content.clone());
+            }
+        }
+        MetaTags(tags)
+    }
+
+    pub(crate) fn all(&self, keys: &[&str]) -> Vec<String> {
+        keys.iter().flat_map(|key| self.0.get(*key).into_iter().flatten()).cloned().collect()
+    }
+
+    pub(crate) fn first(&self, keys: &[&str]) -> Option<String> {
+        self.all(keys).into_iter().next()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpenGraph {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub url: Option<String>,
+    pub site_name: Option<String>,
+    /// `og:type`, e.g. `article`.
+    pub kind: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TwitterCard {
+    pub card: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// The `@username` of the publishing site.
+    pub site: Option<String>,
+}
+
+/// Metadata declared in the `<meta>` and `<link>` tags of a page. Urls are
+/// returned as written in the markup.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub open_graph: OpenGraph,
+    pub twitter: TwitterCard,
+    /// `<meta name="description">`.
+    pub description: Option<String>,
+    pub canonical: Option<String>,
+    pub amphtml: Option<String>,
+    pub favicon: Option<String>,
+    /// `<link rel="image_src">`, the pre-OpenGraph way to name a share image.
+    pub image_src: Option<String>,
+    pub(crate) tags: MetaTags,
+}
+
+/// `href`s of the `<link>` elements by their lowercased `rel` tokens, in
+/// document order.
+fn links(root: &Handle) -> HashMap<String, Vec<String>> {
+    let mut links: HashMap<String, Vec<String>> = HashMap::new();
+    for link in root.borrow().descendants().filter(|node| {
+        let node = node.borrow();
+        node.is_element_node() && node.tag_name() == "link"
+    }) {
+        let link = link.borrow();
+        let href = match link.attr("href").map(|s| s.trim().to_string()) {
+            Some(href) if !href.is_empty() => href,
+            _ => continue,
+        };
+        let rel = link.attr("rel").unwrap_or_default().to_lowercase();
+        // "shortcut icon" is a single relation, not two.
+        let tokens = if rel.split_whitespace().eq(["shortcut", "icon"]) {
+            vec!["icon".to_string()]
+        } else {
+            rel.split_whitespace().map(str::to_string).collect()
+        };
+        for token in tokens {
+            links.entry(token).or_default().push(href.clone());
+        }
+    }
+    links
+}
+
+impl Metadata {
+    pub fn new(root: &Handle) -> Metadata {
+        let tags = MetaTags::new(root);
+        let links = links(root);
+        let link = |rels: &[&str]| rels.iter().find_map(|rel| links.get(*rel).and_then(|hrefs| hrefs.first()).cloned());
+        Metadata {
+            open_graph: OpenGraph {
+                title: tags.first(&["og:title"]),
+                description: tags.first(&["og:description"]),
+                image: tags.first(&["og:image:secure_url", "og:image:url", "og:image"]),
+                url: tags.first(&["og:url"]),
+                site_name: tags.first(&["og:site_name"]),
+                kind: tags.first(&["og:type"]),
+                locale: tags.first(&["og:locale"]),
+            },
+            twitter: TwitterCard {
+                card: tags.first(&["twitter:card"]),
+                title: tags.first(&["twitter:title"]),
+                description: tags.first(&["twitter:description"]),
+                image: tags.first(&["twitter:image", "twitter:image:src"]),
+                site: tags.first(&["twitter:site"]),
+            },
+            description: tags.first(&["description"]),
+            canonical: link(&["canonical"]),
+            amphtml: link(&["amphtml"]),
+            favicon: link(&["icon", "apple-touch-icon", "apple-touch-icon-precomposed"]),
+            image_src: link(&["image_src"]),
+            tags,
+        }
+    }
+
+    /// The share title: OpenGraph, then Twitter Card.
+    pub fn title(&self) -> Option<&str> {
+        self.open_graph.title.as_deref().or(self.twitter.title.as_deref())
+    }
+
+    /// The share image: OpenGraph, then Twitter Card, then `image_src`.
+    pub fn image(&self) -> Option<&str> {
+        self.open_graph.image.as_deref()
+            .or(self.twitter.image.as_deref())
+            .or(self.image_src.as_deref())
+    }
+
+    /// OpenGraph, then Twitter Card, then the meta description.
+    pub fn description(&self) -> Option<&str> {
+        self.open_graph.description.as_deref()
+            .or(self.twitter.description.as_deref())
+            .or(self.description.as_deref())
+    }
+
+    /// `<link rel="canonical">`, then `og:url`.
+    pub fn canonical_url(&self) -> Option<&str> {
+        self.canonical.as_deref().or(self.open_graph.url.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::Metadata;
+
+    #[test]
+    fn test_metadata() {
+        let html = r#"<html><head>
+<meta property="og:title" content="OG  title"><meta name="twitter:title" content="Twitter title">
+<meta name="twitter:image:src" content="https://example.com/t.jpg">
+<meta name="Description" content="Meta description">
+<meta property="og:url" content="https://example.com/og">
+<link rel="amphtml" href="https://example.com/amp"><link rel="Shortcut Icon" href="/favicon.ico">
+<link rel="apple-touch-icon" href="/touch.png">
+</head><body></body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let metadata = Metadata::new(&doc.document.borrow().root());
+        assert_eq!(metadata.title(), Some("OG title"));
+        assert_eq!(metadata.image(), Some("https://example.com/t.jpg"));
+        assert_eq!(metadata.description(), Some("Meta description"));
+        assert_eq!(metadata.canonical_url(), Some("https://example.com/og"));
+        assert_eq!(metadata.amphtml.as_deref(), Some("https://example.com/amp"));
+        assert_eq!(metadata.favicon.as_deref(), Some("/favicon.ico"));
+    }
+
+    #[test]
+    fn test_canonical_link() {
+        let html = r#"<html><head><link rel="canonical" href=" https://example.com/a "><meta property="og:url" content="https://example.com/og">
+<meta property="og:image" content="https://example.com/a.jpg"><meta property="og:image:secure_url" content="https://example.com/s.jpg">
+</head><body></body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let metadata = Metadata::new(&doc.document.borrow().root());
+        assert_eq!(metadata.canonical_url(), Some("https://example.com/a"));
+        assert_eq!(metadata.image(), Some("https://example.com/s.jpg"));
+        assert!(metadata.title().is_none());
+    }
+}
@@ -4,9 +4,26 @@ use serde::{Serialize, Deserialize};
 pub struct Feature {
     pub url: String,
     pub title: String,
+    pub site_name: String,
     pub image: String,
+    pub images: Vec<Image>,
     pub content: String,
+    pub blocks: Vec<Block>,
+    pub content_html: String,
+    pub content_markdown: String,
     pub feature: String,
+    pub authors: Vec<String>,
+    pub published_time: String,
+    pub modified_time: String,
+    pub section: String,
+    pub keywords: Vec<String>,
+    pub language: String,
+    pub publisher: String,
+    pub description: String,
+    pub canonical_url: String,
+    pub amp_url: String,
+    pub favicon: String,
+    pub site_rule: String,
 }
 
 impl Default for Feature {
@@ -14,16 +31,100 @@ impl Default for Feature {
         Self { 
             url: Default::default(), 
             title: Default::default(), 
+            site_name: Default::default(),
             image: Default::default(),
+            images: Default::default(),
             content: Default::default(),
+            blocks: Default::default(),
+            content_html: Default::default(),
+            content_markdown: Default::default(),
             feature: Default::default(),
+            authors: Default::default(),
+            published_time: Default::default(),
+            modified_time: Default::default(),
+            section: Default::default(),
+            keywords: Default::default(),
+            language: Default::default(),
+            publisher: Default::default(),
+            description: Default::default(),
+            canonical_url: Default::default(),
+            amp_url: Default::default(),
+            favicon: Default::default(),
+            site_rule: Default::default(),
         }
     }
 }
 
+impl Feature {
+    /// The article as a Markdown document: the headline, a byline with the
+    /// authors and publication time when known, then the content.
+    pub fn to_markdown(&self) -> String {
+        let mut parts = vec![format!("# {}", self.title)];
+        let byline = [self.authors.join(", "), self.published_time.clone()].into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        if !byline.is_empty() {
+            parts.push(format!("*{}*", byline.join(" · ")));
+        }
+        if !self.content_markdown.is_empty() {
+            parts.push(self.content_markdown.clone());
+        }
+        parts.join("\n\n") + "\n"
+    }
+
+    /// The article as a standalone html document around the sanitized content.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n");
+        if self.language.is_empty() {
+            html.push_str("<html>\n");
+        } else {
+            html.push_str(&format!("<html lang=\"{}\">\n", escape_html(&self.language)));
+        }
+        html.push_str("<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{}</title>\n", escape_html(&self.title)));
+        if !self.canonical_url.is_empty() {
+            html.push_str(&format!("<link rel=\"canonical\" href=\"{}\">\n", escape_html(&self.canonical_url)));
+        }
+        if !self.description.is_empty() {
+            html.push_str(&format!("<meta name=\"description\" content=\"{}\">\n", escape_html(&self.description)));
+        }
+        html.push_str("</head>\n<body>\n<article>\n");
+        html.push_str(&format!("<h1>{}</h1>\n", escape_html(&self.title)));
+        let byline = [self.authors.join(", "), self.published_time.clone()].into_iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| escape_html(&s))
+            .collect::<Vec<_>>();
+        if !byline.is_empty() {
+            html.push_str(&format!("<p class=\"byline\">{}</p>\n", byline.join(" · ")));
+        }
+        if !self.content_html.is_empty() {
+            html.push_str(&self.content_html);
+            html.push('\n');
+        }
+        html.push_str("</article>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
 use regex::Regex;
-use render_dom::Handle;
-use serde_json::Value;
+use render_dom::{Handle, Node};
+
+use crate::article::{find_article_meta, split_authors};
+use crate::blocks::{Block, BlockBuilder};
+use crate::clean::HtmlCleaner;
+use crate::content::{ContentScorer, find_content_node};
+use crate::error::Error;
+use crate::images::{find_images, Image};
+use crate::ld_json::{Article, LdJson};
+use crate::metadata::Metadata;
+use crate::rules::{CompiledRule, SiteRules};
+use crate::title::find_headline;
+use crate::urls::UrlResolver;
+use crate::vision::VisualSegmenter;
 
 
 fn collapse_str(s: &str) -> String {
@@ -31,76 +132,161 @@ fn collapse_str(s: &str) -> String {
     re.replace_all(s, " ").to_string()
 }
 
-fn find_title(root: &Handle) -> Option<String> {
-    root.borrow().title().map(|s| collapse_str(&s))
+/// Collapses every run of whitespace to a single space and trims the ends.
+pub(crate) fn normalize(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-fn find_image(root: &Handle) -> Option<String> {
-    root.borrow().ld_json().map_or(None,|s| {
-        let v: Value = serde_json::from_str(&s).ok()?;
-        let result = v.as_object()?;
-        Some(result["image"].as_object()?["url"].as_str()?.to_string())
-    })
+fn non_empty(s: &str) -> Option<String> {
+    Some(collapse_str(s.trim())).filter(|s| !s.is_empty())
 }
 
-fn find_content(root: &Handle) -> Option<String> {
-    let body = root.borrow().body();
-    let main = body.borrow().descendants().find(|child| {
-        if !child.borrow().is_element_node() {
-            return false;
-        }
-        if let Some(_class) = child.borrow().attr("class") {
-            if _class.contains("article-body") {
-                return true;
-            }
-        }
+/// JSON-LD images, then the lead image of the page, then the
+/// OpenGraph/Twitter/`image_src` share image.
+fn find_image(ld_json: &LdJson, images: &[Image], metadata: &Metadata) -> Option<String> {
+    ld_json.images().into_iter().next()
+        .or_else(|| images.first().map(|image| image.src.clone()))
+        .or_else(|| metadata.image().and_then(non_empty))
+}
+
+/// JSON-LD description, then the OpenGraph/Twitter/meta description.
+fn find_description(article: Option<&Article>, metadata: &Metadata) -> Option<String> {
+    article.and_then(|article| non_empty(&article.description))
+        .or_else(|| metadata.description().and_then(non_empty))
+}
+
+/// `<link rel="canonical">`, then `og:url`, then the JSON-LD url.
+fn find_canonical_url(article: Option<&Article>, metadata: &Metadata) -> Option<String> {
+    metadata.canonical_url().and_then(non_empty)
+        .or_else(|| article.and_then(|article| non_empty(&article.url)))
+}
+
+/// Whether a node inside the content is left out of the extracted text. The
+/// removal selectors of a site rule replace the built-in publisher heuristics.
+fn is_rubbish(node: &Node, scorer: &ContentScorer, segmenter: Option<&VisualSegmenter>, rule: Option<&CompiledRule>) -> bool {
+    if !node.is_element_node() {
         return false;
-    });
-    let node = main.unwrap_or(body);
-    let borrow = node.borrow();
-    Some(collapse_str(&borrow.descendants_text_skip(|node| {
-        if !node.is_element_node() {
-            return false;
-        }
-        if node.is_none_tag() {
+    }
+    if node.is_none_tag() || scorer.is_unlikely(node) {
+        return true;
+    }
+    if segmenter.is_some_and(|segmenter| segmenter.is_hidden(node)) {
+        return true;
+    }
+    match rule.filter(|rule| rule.has_removals()) {
+        Some(rule) => rule.is_removed(node),
+        None => is_builtin_rubbish(node),
+    }
+}
+
+/// Widgets of publishers without a site rule.
+fn is_builtin_rubbish(node: &Node) -> bool {
+    if let Some(_class) = node.attr("class") {
+        let rubbish_classes = vec!["featured-video", "speechkit-wrapper", "image-ct"];
+        if rubbish_classes.iter().any(|c| _class.contains(c)) {
             return true;
         }
-        if let Some(_class) = node.attr("class") {
-            let rubbish_classes = vec!["featured-video", "speechkit-wrapper", "image-ct"];
-            if rubbish_classes.iter().any(|c| _class.contains(c)) {
-                return true;
-            }
-        }
-        if node.tag_name() == "a" {
-            if let Some(child) = node.first_child() {
-                if child.borrow().is_element_node() && child.borrow().tag_name() == "strong" {
-                    let text = child.borrow().descendants_text();
-                    if !text.is_empty() && text.chars().any(|c| c.is_uppercase()){
-                        return true;
-                    }
+    }
+    if node.tag_name() == "a" {
+        if let Some(child) = node.first_child() {
+            if child.borrow().is_element_node() && child.borrow().tag_name() == "strong" {
+                let text = child.borrow().descendants_text();
+                if !text.is_empty() && text.chars().any(|c| c.is_uppercase()){
+                    return true;
                 }
             }
         }
-        false
-    })).trim().to_string())
+    }
+    false
+}
+
+fn find_content(node: &Handle, scorer: &ContentScorer, segmenter: Option<&VisualSegmenter>, rule: Option<&CompiledRule>) -> String {
+    let borrow = node.borrow();
+    collapse_str(&borrow.descendants_text_skip(|node| is_rubbish(node, scorer, segmenter, rule))).trim().to_string()
 }
 
 fn find_feature(root: &Handle) -> Option<String> {
-    let body = root.borrow().body();
+    let body = root.borrow().try_body()?;
     let borrow = body.borrow();
     let vision = &borrow.custom_node_data().vision;
     Some(format!("{}:{}:{}:{}:{}", vision.width, vision.height, vision.xpos, vision.ypos, vision.visible))
 }
 
-pub(crate) fn parse_tree(url: String, root: Handle) -> Result<Feature, String>{
+pub(crate) fn parse_tree(url: String, root: Handle, rules: &SiteRules) -> Result<Feature, Error>{
     let mut feature = Feature::default();
     feature.url = url;
     let root = &root;
-    feature.title = find_title(root).ok_or("not found title".to_string())?;
-    if let Some(image) = find_image(root) {
-        feature.image = image;
+    let rule = rules.find(&feature.url);
+    feature.site_rule = rule.map(|rule| rule.name().to_string()).unwrap_or_default();
+    let resolver = UrlResolver::new(&feature.url, root);
+    let ld_json = LdJson::new(root);
+    let article = ld_json.article();
+    let metadata = Metadata::new(root);
+    let headline = find_headline(root, &ld_json, article.as_ref(), &metadata).unwrap_or_default();
+    feature.title = rule.and_then(|rule| rule.find_title(root))
+        .or(Some(headline.title).filter(|title| !title.is_empty()))
+        .ok_or(Error::MissingTitle)?;
+    feature.site_name = headline.site_name;
+    let body = root.borrow().try_body().ok_or(Error::MissingContent)?;
+    let scorer = ContentScorer::new();
+    let segmenter = VisualSegmenter::new(&body);
+    let content = rule.and_then(|rule| rule.find_content(&body))
+        .unwrap_or_else(|| find_content_node(&body, &scorer, segmenter.as_ref()));
+    if let Some(rule) = rule {
+        rule.rewrite_text(&content);
+    }
+    feature.content = find_content(&content, &scorer, segmenter.as_ref(), rule);
+    feature.blocks = BlockBuilder::new(|node| is_rubbish(node, &scorer, segmenter.as_ref(), rule), &resolver).build(&content);
+    let cleaned = HtmlCleaner::new(|node| is_rubbish(node, &scorer, segmenter.as_ref(), rule), &resolver).clean_tree(&content);
+    feature.content_html = cleaned.borrow().inner_html().trim().to_string();
+    feature.content_markdown = cleaned.borrow().markdown();
+    feature.images = find_images(&body, &content, segmenter.as_ref());
+    for image in feature.images.iter_mut() {
+        image.src = resolver.resolve(&image.src);
+    }
+    if let Some(image) = find_image(&ld_json, &feature.images, &metadata) {
+        feature.image = resolver.resolve(&image);
     }
-    feature.content = find_content(root).ok_or("not found content".to_string())?;
-    feature.feature = find_feature(root).ok_or("not found feature".to_string())?;
+    feature.feature = find_feature(root).ok_or(Error::MissingFeature)?;
+    feature.description = find_description(article.as_ref(), &metadata).unwrap_or_default();
+    feature.canonical_url = resolver.resolve(&find_canonical_url(article.as_ref(), &metadata).unwrap_or_else(|| feature.url.clone()));
+    feature.amp_url = resolver.resolve(&metadata.amphtml.clone().unwrap_or_default());
+    feature.favicon = resolver.resolve(&metadata.favicon.clone().unwrap_or_default());
+    let meta = find_article_meta(root, &ld_json, &metadata);
+    let rule_authors = rule.map(|rule| rule.find_authors(root)).unwrap_or_default();
+    feature.authors = if rule_authors.is_empty() {
+        meta.authors
+    } else {
+        rule_authors.iter().flat_map(|byline| split_authors(byline)).collect()
+    };
+    feature.published_time = rule.and_then(|rule| rule.find_date(root)).unwrap_or(meta.published_time);
+    feature.modified_time = meta.modified_time;
+    feature.section = meta.section;
+    feature.keywords = meta.keywords;
+    feature.language = meta.language;
+    feature.publisher = meta.publisher;
     Ok(feature)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Feature;
+
+    #[test]
+    fn test_to_html_escapes() {
+        let feature = Feature {
+            title: "Cats & <Dogs>".to_string(),
+            description: r#"Say "hi""#.to_string(),
+            language: r#"en"><script>"#.to_string(),
+            content_html: "<p>Text</p>".to_string(),
+            ..Feature::default()
+        };
+        let html = feature.to_html();
+        assert!(html.contains(r#"<html lang="en&quot;&gt;&lt;script&gt;">"#));
+        assert!(html.contains("<title>Cats &amp; &lt;Dogs&gt;</title>"));
+        assert!(html.contains("<h1>Cats &amp; &lt;Dogs&gt;</h1>"));
+        assert!(html.contains(r#"<meta name="description" content="Say &quot;hi&quot;">"#));
+        assert!(html.contains("<p>Text</p>"));
+        assert!(!html.contains("<script>"));
+    }
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use render_dom::{Handle, Node, Selector};
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+use crate::error::RuleError;
+use crate::parser::normalize;
+
+
+/// A regex replacement applied to the extracted text.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Replace {
+    pub pattern: String,
+    #[serde(default)]
+    pub with: String,
+}
+
+/// Extraction rules for one site, as written in a rule file.
+///
+/// A rule applies to pages on one of its `domains` (subdomains included) or
+/// whose url matches one of its `url_patterns`. Selectors are CSS selectors;
+/// the first one with a match wins, except for `remove` and `author` where all
+/// matches are used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SiteRule {
+    pub name: String,
+    pub domains: Vec<String>,
+    pub url_patterns: Vec<String>,
+    /// The element holding the article body.
+    pub content: Vec<String>,
+    /// Elements left out of the content.
+    pub remove: Vec<String>,
+    pub title: Vec<String>,
+    pub author: Vec<String>,
+    /// Elements with the publication time, in their `datetime` or `content`
+    /// attribute or their text.
+    pub date: Vec<String>,
+    /// Replacements applied in order to each text node of the content before
+    /// the text, blocks, html and Markdown are built from it, so a pattern
+    /// cannot match across elements.
+    pub replace: Vec<Replace>,
+}
+
+/// The layout of a rule file: `[[rules]]` tables in TOML, `{"rules": [...]}` in JSON.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<SiteRule>,
+}
+
+/// A validated rule with its selectors and patterns parsed.
+#[derive(Debug)]
+pub struct CompiledRule {
+    rule: SiteRule,
+    url_patterns: Vec<Regex>,
+    content: Vec<Selector>,
+    remove: Vec<Selector>,
+    title: Vec<Selector>,
+    author: Vec<Selector>,
+    date: Vec<Selector>,
+    replace: Vec<(Regex, String)>,
+}
+
+fn text_of(node: &Node) -> String {
+    normalize(&node.descendants_text_skip(|node| node.is_element_node() && node.is_none_tag()))
+}
+
+fn parse_selectors(rule: &str, selectors: &[String]) -> Result<Vec<Selector>, RuleError> {
+    selectors.iter()
+        .map(|selector| Selector::parse(selector).map_err(|err| RuleError::Invalid { rule: rule.to_string(), message: err.to_string() }))
+        .collect()
+}
+
+fn parse_regex(rule: &str, pattern: &str) -> Result<Regex, RuleError> {
+    Regex::new(pattern).map_err(|err| RuleError::Invalid { rule: rule.to_string(), message: err.to_string() })
+}
+
+impl CompiledRule {
+    /// Validates the `index`th rule of a file, counting from 0.
+    fn new(index: usize, rule: SiteRule) -> Result<CompiledRule, RuleError> {
+        let name = rule.name.as_str();
+        if name.trim().is_empty() {
+            return Err(RuleError::Invalid { rule: format!("#{}", index + 1), message: "rule has no name".to_string() });
+        }
+        if rule.domains.iter().all(|domain| domain.trim().is_empty()) && rule.url_patterns.is_empty() {
+            return Err(RuleError::Invalid { rule: name.to_string(), message: "rule has no domains or url_patterns".to_string() });
+        }
+        Ok(CompiledRule {
+            url_patterns: rule.url_patterns.iter().map(|pattern| parse_regex(name, pattern)).collect::<Result<_, _>>()?,
+            content: parse_selectors(name, &rule.content)?,
+            remove: parse_selectors(name, &rule.remove)?,
+            title: parse_selectors(name, &rule.title)?,
+            author: parse_selectors(name, &rule.author)?,
+            date: parse_selectors(name, &rule.date)?,
+            replace: rule.replace.iter()
+                .map(|replace| Ok((parse_regex(name, &replace.pattern)?, replace.with.clone())))
+                .collect::<Result<_, _>>()?,
+            rule,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.rule.name
+    }
+
+    pub fn rule(&self) -> &SiteRule {
+        &self.rule
+    }
+
+    fn matches(&self, url: &str, host: &str) -> bool {
+        let on_domain = !host.is_empty() && self.rule.domains.iter().any(|domain| {
+            let domain = domain.trim().trim_start_matches('.').to_lowercase();
+            !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+        });
+        on_domain || self.url_patterns.iter().any(|pattern| pattern.is_match(url))
+    }
+
+    fn first_match(&self, selectors: &[Selector], root: &Handle) -> Option<Handle> {
+        let root = root.borrow();
+        selectors.iter().find_map(|selector| {
+            root.descendants().find(|node| selector.matches(&node.borrow()))
+        })
+    }
+
+    /// Whether the rule removes `node` from the content.
+    pub(crate) fn is_removed(&self, node: &Node) -> bool {
+        self.remove.iter().any(|selector| selector.matches(node))
+    }
+
+    pub(crate) fn has_removals(&self) -> bool {
+        !self.remove.is_empty()
+    }
+
+    pub(crate) fn find_content(&self, body: &Handle) -> Option<Handle> {
+        self.first_match(&self.content, body)
+    }
+
+    pub(crate) fn find_title(&self, root: &Handle) -> Option<String> {
+        self.first_match(&self.title, root).map(|node| text_of(&node.borrow())).filter(|title| !title.is_empty())
+    }
+
+    /// Texts of all the author elements, for the first selector with a match.
+    pub(crate) fn find_authors(&self, root: &Handle) -> Vec<String> {
+        let root = root.borrow();
+        self.author.iter()
+            .map(|selector| root.descendants()
+                .filter(|node| selector.matches(&node.borrow()))
+                .map(|node| text_of(&node.borrow()))
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>())
+            .find(|authors| !authors.is_empty())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn find_date(&self, root: &Handle) -> Option<String> {
+        let node = self.first_match(&self.date, root)?;
+        let node = node.borrow();
+        node.attr("datetime").or_else(|| node.attr("content"))
+            .map(|s| normalize(&s))
+            .filter(|s| !s.is_empty())
+            .or_else(|| Some(text_of(&node)).filter(|s| !s.is_empty()))
+    }
+
+    /// Applies the text replacements to `text`.
+    pub(crate) fn replace_text(&self, text: &str) -> String {
+        self.replace.iter().fold(text.to_string(), |text, (pattern, with)| {
+            pattern.replace_all(&text, with.as_str()).to_string()
+        })
+    }
+
+    /// Applies the text replacements to the text nodes under `content`.
+    pub(crate) fn rewrite_text(&self, content: &Handle) {
+        if self.replace.is_empty() {
+            return;
+        }
+        let texts = content.borrow().descendants().filter(|node| node.borrow().is_text_node()).collect::<Vec<_>>();
+        for node in texts {
+            let text = node.borrow().text();
+            let replaced = self.replace_text(&text);
+            if replaced != text {
+                node.borrow_mut().set_text(replaced);
+            }
+        }
+    }
+}
+
+/// Site rules in the order they were loaded; the first rule matching a page
+/// applies to it. Pages without a rule are extracted with the built-in
+/// heuristics only.
+#[derive(Debug, Default)]
+pub struct SiteRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl SiteRules {
+    pub fn new(rules: Vec<SiteRule>) -> Result<SiteRules, RuleError> {
+        let rules = rules.into_iter().enumerate().map(|(i, rule)| CompiledRule::new(i, rule)).collect::<Result<_, _>>()?;
+        Ok(SiteRules { rules })
+    }
+
+    pub fn from_toml(s: &str) -> Result<SiteRules, RuleError> {
+        let file: RuleFile = toml::from_str(s).map_err(|err| RuleError::Parse { path: String::new(), message: err.to_string() })?;
+        SiteRules::new(file.rules)
+    }
+
+    pub fn from_json(s: &str) -> Result<SiteRules, RuleError> {
+        let file: RuleFile = serde_json::from_str(s).map_err(|err| RuleError::Parse { path: String::new(), message: err.to_string() })?;
+        SiteRules::new(file.rules)
+    }
+
+    /// Loads a `.toml` or `.json` rule file.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<SiteRules, RuleError> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path).map_err(|err| RuleError::Io { path: path.display().to_string(), message: err.to_string() })?;
+        SiteRules::parse_file(path, &s)
+    }
+
+    /// Parses the contents `s` of the rule file at `path`, as TOML or JSON by
+    /// its extension.
+    pub fn parse_file<P: AsRef<Path>>(path: P, s: &str) -> Result<SiteRules, RuleError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let rules = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => SiteRules::from_toml(s),
+            Some("json") => SiteRules::from_json(s),
+            _ => Err(RuleError::Parse { path: String::new(), message: "expected a .toml or .json file".to_string() }),
+        };
+        rules.map_err(|err| match err {
+            RuleError::Parse { message, .. } => RuleError::Parse { path: name, message },
+            RuleError::Invalid { rule, message } => RuleError::Invalid { rule: format!("{} in {}", rule, name), message },
+            err => err,
+        })
+    }
+
+    /// The `.toml` and `.json` files of a directory, in file name order.
+    pub fn files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, RuleError> {
+        let dir = dir.as_ref();
+        let io_error = |err: std::io::Error| RuleError::Io { path: dir.display().to_string(), message: err.to_string() };
+        let mut paths = fs::read_dir(dir).map_err(io_error)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io_error)?;
+        paths.retain(|path| path.is_file() && matches!(path.extension().and_then(|ext| ext.to_str()), Some("toml" | "json")));
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Loads the `.toml` and `.json` files of a directory, in file name order.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<SiteRules, RuleError> {
+        let mut rules = SiteRules::default();
+        for path in SiteRules::files(dir)? {
+            rules.extend(SiteRules::load_file(&path)?);
+        }
+        Ok(rules)
+    }
+
+    /// Loads a rule file, or all the rule files of a directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SiteRules, RuleError> {
+        if path.as_ref().is_dir() {
+            SiteRules::load_dir(path)
+        } else {
+            SiteRules::load_file(path)
+        }
+    }
+
+    /// Appends `other`, whose rules then apply after the current ones.
+    pub fn extend(&mut self, other: SiteRules) {
+        self.rules.extend(other.rules);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CompiledRule> {
+        self.rules.iter()
+    }
+
+    /// The rule for a page url.
+    pub fn find(&self, url: &str) -> Option<&CompiledRule> {
+        let host = Url::parse(url.trim()).ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .unwrap_or_default();
+        self.rules.iter().find(|rule| rule.matches(url, &host))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::SiteRules;
+    use crate::error::RuleError;
+
+    #[test]
+    fn test_load_and_match() {
+        let rules = SiteRules::from_toml(r#"
+[[rules]]
+name = "daily"
+domains = ["dailynews.com"]
+content = [".story-body"]
+remove = [".related", "aside"]
+title = ["h1.headline"]
+author = [".byline .name"]
+date = ["time.published"]
+replace = [{ pattern = "\\s*Read more\\.?$" }]
+
+[[rules]]
+name = "blog"
+url_patterns = ["^https?://[^/]+/blog/"]
+"#).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules.find("https://www.DailyNews.com/a.html").map(|rule| rule.name()), Some("daily"));
+        assert_eq!(rules.find("https://example.com/blog/post").map(|rule| rule.name()), Some("blog"));
+        assert!(rules.find("https://notdailynews.com/a.html").is_none());
+
+        let rule = rules.find("https://dailynews.com/a.html").unwrap();
+        let html = r#"<html><body><h1 class="headline"> The  title </h1>
+<p class="byline"><span class="name">Jane Doe</span> and <span class="name">John Roe</span></p>
+<time class="published" datetime="2022-03-01">March 1</time>
+<div class="story-body"><p>Text</p><aside>Ad</aside></div></body></html>"#;
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let body = doc.document.borrow().body();
+        assert_eq!(rule.find_title(&root), Some("The title".to_string()));
+        assert_eq!(rule.find_authors(&root), vec!["Jane Doe", "John Roe"]);
+        assert_eq!(rule.find_date(&root), Some("2022-03-01".to_string()));
+        let content = rule.find_content(&body).unwrap();
+        assert_eq!(content.borrow().attr("class"), Some("story-body".to_string()));
+        let aside = content.borrow().select_first("aside").unwrap().unwrap();
+        assert!(rule.is_removed(&aside.borrow()));
+        assert_eq!(rule.replace_text("Some text. Read more."), "Some text.");
+        let doc = parse_document(&mut r#"<html><body><p>Some text. <a href="/more">Read more</a></p></body></html>"#.as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        rule.rewrite_text(&body);
+        assert_eq!(body.borrow().inner_html(), r#"<p>Some text. <a href="/more"></a></p>"#);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let err = SiteRules::from_json(r#"{"rules": [{"name": "bad", "domains": ["a.com"], "content": ["div["]}]}"#).unwrap_err();
+        assert!(matches!(err, RuleError::Invalid { rule, .. } if rule == "bad"));
+        let err = SiteRules::from_json(r#"{"rules": [{"name": "nowhere", "content": ["div"]}]}"#).unwrap_err();
+        assert!(matches!(err, RuleError::Invalid { .. }));
+        let err = SiteRules::from_json(r#"{"rules": [{"name": "ok", "domains": ["a.com"]}, {"domains": ["b.com"]}]}"#).unwrap_err();
+        assert!(matches!(err, RuleError::Invalid { rule, .. } if rule == "#2"));
+        assert!(matches!(SiteRules::from_toml("rules = 1"), Err(RuleError::Parse { .. })));
+    }
+}
@@ -0,0 +1,196 @@
+use regex::Regex;
+use render_dom::Handle;
+
+use crate::ld_json::{Article, LdJson};
+use crate::metadata::Metadata;
+use crate::parser::normalize;
+use crate::vision::VisualSegmenter;
+
+
+/// Separators publishers put between the headline, the site name and the
+/// section in `<title>`; dashes only count when surrounded by spaces.
+const SEPARATOR_PATTERN: &str = r"\s*(?:\||::|»|·|•)\s*|\s+[-–—]\s+";
+
+/// Segments with at most this many words are site or section names rather than
+/// a part of the headline.
+const MAX_SUFFIX_WORDS: usize = 4;
+
+/// The headline of a page and the site name stripped from it.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Headline {
+    pub title: String,
+    pub site_name: String,
+}
+
+/// Lowercased words of a title, for comparing candidates.
+fn words(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn is_similar(a: &str, b: &str) -> bool {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long.contains(&short) && short.len() * 2 >= long.len()
+}
+
+fn word_count(s: &str) -> usize {
+    s.split_whitespace().count()
+}
+
+/// Splits a title into its trimmed, non-empty segments.
+fn segments<'a>(title: &'a str, separator: &Regex) -> Vec<&'a str> {
+    separator.split(title).map(str::trim).filter(|s| !s.is_empty()).collect()
+}
+
+/// Removes the site name and short section names around the headline in a
+/// title such as "Headline | Site Name - Section".
+fn strip_site_name(title: &str, site_name: &str, separator: &Regex) -> String {
+    let title = normalize(title);
+    let parts = segments(&title, separator);
+    if parts.len() < 2 {
+        return title;
+    }
+    let mut kept = parts.iter().enumerate()
+        .filter(|(_, part)| site_name.is_empty() || !is_similar(part, site_name))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let longest = kept.iter().map(|&i| word_count(parts[i])).max().unwrap_or_default();
+    let max_words = MAX_SUFFIX_WORDS.min(longest.saturating_sub(1));
+    let count = kept.len();
+    while kept.len() > 1 && word_count(parts[kept[kept.len() - 1]]) <= max_words {
+        kept.pop();
+    }
+    // A site name leads the title only when none trails it.
+    while kept.len() == count && count > 1 && word_count(parts[kept[0]]) <= max_words {
+        kept.remove(0);
+    }
+    match (kept.first(), kept.last()) {
+        // Keep the original separators inside the headline.
+        (Some(&first), Some(&last)) => {
+            let start = parts[first].as_ptr() as usize - title.as_ptr() as usize;
+            let end = parts[last].as_ptr() as usize - title.as_ptr() as usize + parts[last].len();
+            title[start..end].to_string()
+        },
+        _ => title,
+    }
+}
+
+/// Guesses the site name from the segments of `<title>` around the headline.
+fn infer_site_name(title: &str, headline: &str, separator: &Regex) -> String {
+    let title = normalize(title);
+    let parts = segments(&title, separator);
+    if parts.len() < 2 {
+        return String::new();
+    }
+    let candidate = if is_similar(parts[parts.len() - 1], headline) { parts[0] } else { parts[parts.len() - 1] };
+    if word_count(candidate) <= MAX_SUFFIX_WORDS && !is_similar(candidate, headline) {
+        candidate.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Text of the most prominent visible `<h1>`: the largest rendered font, then
+/// the highest on the page, or simply the first one without render info.
+fn find_h1(root: &Handle) -> Option<String> {
+    let body = root.borrow().try_body()?;
+    let segmenter = VisualSegmenter::new(&body);
+    let mut headings = body.borrow().select("h1").ok()?.into_iter()
+        .filter(|h1| !segmenter.as_ref().is_some_and(|segmenter| segmenter.is_hidden(&h1.borrow())))
+        .map(|h1| {
+            let h1 = h1.borrow();
+            let data = h1.custom_node_data();
+            let text = normalize(&h1.descendants_text_skip(|node| node.is_element_node() && node.is_none_tag()));
+            (text, data.style.font_size(), data.vision.ypos)
+        })
+        .filter(|(text, _, _)| !text.is_empty())
+        .collect::<Vec<_>>();
+    if segmenter.is_some() {
+        headings.sort_by_key(|(_, font_size, ypos)| (-font_size, *ypos));
+    }
+    headings.into_iter().next().map(|(text, _, _)| text)
+}
+
+/// Picks the headline among the JSON-LD headline, the OpenGraph and Twitter
+/// titles, `<title>` and the page's `<h1>`, with the site name stripped.
+///
+/// A visible `<h1>` matching one of the declared titles wins, as it is what
+/// readers see; otherwise the declared titles are ranked in that order.
+pub(crate) fn find_headline(root: &Handle, ld_json: &LdJson, article: Option<&Article>, metadata: &Metadata) -> Option<Headline> {
+    let separator = Regex::new(SEPARATOR_PATTERN).unwrap();
+    let mut site_name = metadata.open_graph.site_name.clone()
+        .or_else(|| article.and_then(|article| article.publisher.as_ref()).map(|publisher| publisher.name.clone()))
+        .or_else(|| ld_json.organization().map(|organization| organization.name))
+        .or_else(|| metadata.tags.first(&["application-name"]))
+        .map(|s| normalize(&s))
+        .unwrap_or_default();
+    let declared = [
+        article.map(|article| article.headline.clone()),
+        metadata.open_graph.title.clone(),
+        metadata.twitter.title.clone(),
+    ].into_iter().flatten()
+        .map(|title| strip_site_name(&title, &site_name, &separator))
+        .filter(|title| !title.is_empty())
+        .collect::<Vec<_>>();
+    let page_title = root.borrow().title().map(|title| normalize(&title)).filter(|title| !title.is_empty());
+    let stripped_title = page_title.as_ref().map(|title| strip_site_name(title, &site_name, &separator));
+    let h1 = find_h1(root);
+
+    let title = h1.clone()
+        .filter(|h1| declared.iter().chain(stripped_title.iter()).any(|title| is_similar(h1, title)))
+        .or_else(|| declared.first().cloned())
+        .or(stripped_title)
+        .or(h1)?;
+    if site_name.is_empty() {
+        site_name = page_title.map(|page_title| infer_site_name(&page_title, &title, &separator)).unwrap_or_default();
+    }
+    Some(Headline { title, site_name })
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use render_dom::parse_document;
+
+    use super::{find_headline, strip_site_name, Headline, SEPARATOR_PATTERN};
+    use crate::ld_json::LdJson;
+    use crate::metadata::Metadata;
+
+    fn headline(html: &str) -> Option<Headline> {
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        let ld_json = LdJson::new(&root);
+        find_headline(&root, &ld_json, ld_json.article().as_ref(), &Metadata::new(&root))
+    }
+
+    #[test]
+    fn test_strip_site_name() {
+        let separator = Regex::new(SEPARATOR_PATTERN).unwrap();
+        assert_eq!(strip_site_name("Markets rally as rates fall | Daily News - Business", "Daily News", &separator),
+            "Markets rally as rates fall");
+        assert_eq!(strip_site_name("Daily News » Markets rally as rates fall", "", &separator), "Markets rally as rates fall");
+        assert_eq!(strip_site_name("Well-known  facts - a long story about them | Site", "", &separator),
+            "Well-known facts - a long story about them");
+        assert_eq!(strip_site_name("Short | Title", "", &separator), "Short | Title");
+    }
+
+    #[test]
+    fn test_find_headline() {
+        let found = headline(r#"<html><head><title>Markets rally as rates fall | Daily News</title>
+<meta property="og:title" content="Markets rally as rates fall - Daily News"></head>
+<body><h1 class="logo">Daily News</h1></body></html>"#).unwrap();
+        assert_eq!(found, Headline { title: "Markets rally as rates fall".to_string(), site_name: "Daily News".to_string() });
+
+        let found = headline(r#"<html><head><title>Daily News: markets</title></head>
+<body><h1>Markets rally as rates fall, lifting stocks</h1></body></html>"#).unwrap();
+        assert_eq!(found.title, "Daily News: markets");
+
+        let found = headline(r#"<html><head><title>Markets rally as rates fall | News</title>
+<meta property="og:site_name" content="Daily News"></head>
+<body><h1>Markets rally as rates fall again</h1></body></html>"#).unwrap();
+        assert_eq!(found, Headline { title: "Markets rally as rates fall again".to_string(), site_name: "Daily News".to_string() });
+    }
+}
@@ -0,0 +1,80 @@
+use render_dom::Handle;
+use url::Url;
+
+
+/// Resolves urls found in a page against its address, honoring `<base href>`.
+pub(crate) struct UrlResolver {
+    base: Option<Url>,
+}
+
+/// Drops the fragment of an absolute url; the `url` crate already lowercases
+/// the scheme and host and removes default ports when parsing.
+fn normalize(mut url: Url) -> String {
+    url.set_fragment(None);
+    url.to_string()
+}
+
+impl UrlResolver {
+    pub(crate) fn new(page_url: &str, root: &Handle) -> UrlResolver {
+        let page_url = Url::parse(page_url.trim()).ok();
+        let base_href = root.borrow().try_head()
+            .and_then(|head| head.borrow().select_first("base[href]").ok().flatten())
+            .and_then(|base| base.borrow().attr("href"));
+        let base = match (page_url, base_href) {
+            (Some(page_url), Some(href)) => page_url.join(href.trim()).ok().or(Some(page_url)),
+            (None, Some(href)) => Url::parse(href.trim()).ok(),
+            (page_url, None) => page_url,
+        };
+        UrlResolver { base }
+    }
+
+    /// Absolute, normalized form of `href`. Urls that cannot be resolved, e.g.
+    /// relative ones on a page whose address is unknown, are returned trimmed.
+    pub(crate) fn resolve(&self, href: &str) -> String {
+        let href = href.trim();
+        if href.is_empty() {
+            return String::new();
+        }
+        let url = match &self.base {
+            Some(base) => base.join(href),
+            None => Url::parse(href),
+        };
+        url.map(normalize).unwrap_or_else(|_| href.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::UrlResolver;
+
+    fn new_resolver(url: &str, head: &str) -> UrlResolver {
+        let html = format!("<html><head>{head}</head><body></body></html>");
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let root = doc.document.borrow().root();
+        UrlResolver::new(url, &root)
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolver = new_resolver("https://Example.COM:443/news/a.html#top", "");
+        assert_eq!(resolver.resolve("img/a.jpg"), "https://example.com/news/img/a.jpg");
+        assert_eq!(resolver.resolve(" /b.jpg#x "), "https://example.com/b.jpg");
+        assert_eq!(resolver.resolve("//cdn.example.com/c.jpg"), "https://cdn.example.com/c.jpg");
+        assert_eq!(resolver.resolve("HTTP://Other.org"), "http://other.org/");
+        assert_eq!(resolver.resolve(""), "");
+    }
+
+    #[test]
+    fn test_base_href() {
+        let resolver = new_resolver("https://example.com/news/a.html", r#"<base href="/static/">"#);
+        assert_eq!(resolver.resolve("a.jpg"), "https://example.com/static/a.jpg");
+
+        let resolver = new_resolver("", r#"<base href="https://example.com/static/">"#);
+        assert_eq!(resolver.resolve("a.jpg"), "https://example.com/static/a.jpg");
+
+        let resolver = new_resolver("not a url", "");
+        assert_eq!(resolver.resolve("a.jpg"), "a.jpg");
+    }
+}
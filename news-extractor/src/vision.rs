@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use render_dom::{Display, Handle, Node, Position};
+
+
+/// Share of the central text a region must hold to be picked as main content.
+const MAIN_REGION_COVERAGE: f64 = 0.8;
+
+/// Minimum amount of central text for the visual segmentation to be trusted.
+const MIN_REGION_TEXT: usize = 140;
+
+/// Leaf blocks whose text is mostly links are navigation, not content.
+const MAX_LINK_DENSITY: f64 = 0.5;
+
+/// Font sizes within this ratio of the dominant one count as body text.
+const FONT_SIZE_TOLERANCE: f64 = 0.25;
+
+/// A leaf block of the visual segmentation: a visible block-level element
+/// without visible block-level children.
+pub(crate) struct VisualBlock {
+    pub node: Handle,
+    pub text_length: usize,
+    pub link_length: usize,
+    pub font_size: i32,
+    pub center_x: i32,
+}
+
+/// VIPS-style segmentation of a rendered page, driven by the `Vision` boxes and
+/// `StyleInfo` the render server attaches to every element.
+pub(crate) struct VisualSegmenter {
+    page_width: i32,
+    page_height: i32,
+}
+
+/// Elements without style info fall back to their tag to decide.
+fn is_block_level(node: &Node) -> bool {
+    let style = &node.custom_node_data().style;
+    style.is_block_level() || (style.display() == Display::Inline && !node.is_inline_tag())
+}
+
+impl VisualSegmenter {
+    /// Returns `None` when the page carries no render geometry, e.g. html that
+    /// did not come from the render server.
+    pub(crate) fn new(body: &Handle) -> Option<VisualSegmenter> {
+        let body = body.borrow();
+        let vision = &body.custom_node_data().vision;
+        if vision.width <= 0 || vision.height <= 0 {
+            return None;
+        }
+        Some(VisualSegmenter {
+            page_width: vision.xpos.max(0) + vision.width,
+            page_height: vision.ypos.max(0) + vision.height,
+        })
+    }
+
+    /// Whether an element is not part of the visible page flow: hidden by the
+    /// renderer, collapsed, fixed to the viewport or laid out off screen.
+    pub(crate) fn is_hidden(&self, node: &Node) -> bool {
+        if !node.is_element_node() {
+            return false;
+        }
+        let data = node.custom_node_data();
+        let vision = &data.vision;
+        if vision.visible == 0 || data.style.is_hidden() || data.style.position() == Position::Fixed {
+            return true;
+        }
+        if vision.width < 0 || vision.height < 0 {
+            // No geometry was recorded for this element.
+            return false;
+        }
+        vision.width == 0 || vision.height == 0
+            || vision.xpos + vision.width <= 0 || vision.ypos + vision.height <= 0
+            || vision.xpos >= self.page_width || vision.ypos >= self.page_height
+    }
+
+    fn visible_text_length(&self, node: &Node) -> usize {
+        node.descendants_text_skip(|node| {
+            node.is_element_node() && (node.is_none_tag() || self.is_hidden(node))
+        }).split_whitespace().map(|word| word.chars().count()).sum()
+    }
+
+    /// Splits the visible part of `body` into leaf blocks that hold text.
+    pub(crate) fn segment(&self, body: &Handle) -> Vec<VisualBlock> {
+        let skip = |node: &Node| node.is_element_node() && (node.is_none_tag() || self.is_hidden(node));
+        body.borrow().descendants_skip(skip)
+            .filter(|handle| {
+                let node = handle.borrow();
+                node.is_element_node() && is_block_level(&node)
+                    && !node.descendants_skip(skip).any(|child| {
+                        let child = child.borrow();
+                        child.is_element_node() && is_block_level(&child)
+                    })
+            })
+            .filter_map(|handle| {
+                let node = handle.borrow();
+                let text_length = self.visible_text_length(&node);
+                if text_length == 0 {
+                    return None;
+                }
+                let link_length = node.descendants_skip(skip)
+                    .filter(|child| {
+                        let child = child.borrow();
+                        child.is_element_node() && child.tag_name() == "a"
+                    })
+                    .map(|link| self.visible_text_length(&link.borrow()))
+                    .sum();
+                let data = node.custom_node_data();
+                let block = VisualBlock {
+                    node: handle.clone(),
+                    text_length,
+                    link_length,
+                    font_size: data.style.font_size(),
+                    center_x: data.vision.xpos + data.vision.width / 2,
+                };
+                Some(block)
+            })
+            .collect()
+    }
+
+    /// Font size carrying the most text, or 0 when the renderer reported none.
+    fn dominant_font_size(blocks: &[&VisualBlock]) -> i32 {
+        let mut sizes = HashMap::new();
+        for block in blocks.iter().filter(|block| block.font_size > 0) {
+            *sizes.entry(block.font_size).or_insert(0) += block.text_length;
+        }
+        sizes.into_iter().max_by_key(|(size, length)| (*length, *size)).map_or(0, |(size, _)| size)
+    }
+
+    fn is_central(&self, block: &VisualBlock) -> bool {
+        let margin = self.page_width / 5;
+        block.center_x >= margin && block.center_x <= self.page_width - margin
+    }
+
+    /// Picks the main content region: the deepest element holding most of the
+    /// text set in the body font within the central column of the page.
+    pub(crate) fn main_region(&self, body: &Handle) -> Option<Handle> {
+        let blocks = self.segment(body);
+        let candidates = blocks.iter()
+            .filter(|block| (block.link_length as f64) < block.text_length as f64 * MAX_LINK_DENSITY)
+            .filter(|block| self.is_central(block))
+            .collect::<Vec<_>>();
+        let font_size = VisualSegmenter::dominant_font_size(&candidates);
+        let content = candidates.into_iter()
+            .filter(|block| {
+                font_size <= 0 || block.font_size <= 0
+                    || ((block.font_size - font_size).abs() as f64) <= font_size as f64 * FONT_SIZE_TOLERANCE
+            })
+            .collect::<Vec<_>>();
+        let total = content.iter().map(|block| block.text_length).sum::<usize>();
+        if total < MIN_REGION_TEXT {
+            return None;
+        }
+
+        let body_ptr = Rc::as_ptr(body);
+        let mut coverage: HashMap<*const _, (Handle, usize, usize)> = HashMap::new();
+        for block in content {
+            let mut ancestors = vec![];
+            let mut current = Some(block.node.clone());
+            while let Some(node) = current {
+                let is_body = Rc::as_ptr(&node) == body_ptr;
+                current = if is_body { None } else { node.borrow().parent() };
+                ancestors.push(node);
+            }
+            let depth = ancestors.len();
+            for (level, node) in ancestors.into_iter().enumerate() {
+                coverage.entry(Rc::as_ptr(&node))
+                    .or_insert_with(|| (node.clone(), depth - level, 0)).2 += block.text_length;
+            }
+        }
+        let required = (total as f64 * MAIN_REGION_COVERAGE).ceil() as usize;
+        coverage.into_values()
+            .filter(|(_, _, length)| *length >= required)
+            .max_by_key(|(_, depth, _)| *depth)
+            .map(|(node, _, _)| node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use render_dom::parse_document;
+
+    use super::VisualSegmenter;
+
+    fn style(display: u32, position: u32, font_size: u32) -> String {
+        let mut items = vec!["0".to_string(); 51];
+        items[22] = font_size.to_string();
+        items[39] = display.to_string();
+        items[43] = position.to_string();
+        items.join(";")
+    }
+
+    fn element(tag: &str, vision: &str, style: &str, class: &str, inner: &str) -> String {
+        format!(r#"<{tag} class="{class}" surface_vision_info="{vision}" dom_style_info="{style}">{inner}</{tag}>"#)
+    }
+
+    #[test]
+    fn test_main_region() {
+        let paragraph = "A long paragraph of article text, set in the body font size of the page, that runs on for a while.";
+        let block = style(2, 0, 16);
+        let html = format!("<html><head></head>{}</html>", element("body", "1000;3000;0;0;1", &block, "", &[
+            element("div", "1000;80;0;0;1", &style(2, 2, 16), "fixed-bar", paragraph),
+            element("div", "200;2000;0;100;1", &block, "sidebar", &element("p", "200;100;0;100;1", &block, "", paragraph)),
+            element("div", "600;2000;200;100;1", &block, "story", &[
+                element("h1", "600;60;200;100;1", &style(2, 0, 32), "", "Headline"),
+                element("p", "600;100;200;200;1", &block, "", paragraph),
+                element("p", "600;100;200;300;1", &block, "", paragraph),
+                element("p", "600;100;200;400;0", &block, "hidden", paragraph),
+            ].concat()),
+            element("div", "600;100;-2000;0;1", &block, "offscreen", paragraph),
+        ].concat()));
+        let doc = parse_document(&mut html.as_bytes()).unwrap();
+        let body = doc.document.borrow().body();
+        let segmenter = VisualSegmenter::new(&body).unwrap();
+
+        let classes = segmenter.segment(&body).iter()
+            .map(|block| block.node.borrow().attr("class").unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(classes, vec!["", "", "", ""]);
+        let region = segmenter.main_region(&body).unwrap();
+        assert_eq!(region.borrow().attr("class"), Some("story".to_string()));
+    }
+
+    #[test]
+    fn test_without_geometry() {
+        let doc = parse_document(&mut "<html><body><p>text</p></body></html>".as_bytes()).unwrap();
+        assert!(VisualSegmenter::new(&doc.document.borrow().body()).is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use std::io::{BufRead, BufReader, Read};
+
+use flate2::bufread::{GzDecoder, MultiGzDecoder, ZlibDecoder};
+
+use crate::error::{WarcError, WarcItemError};
+use crate::parser::Feature;
+use crate::rules::SiteRules;
+
+
+/// A record of a WARC archive, with its block read into memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarcRecord {
+    /// The `WARC-Type` header, e.g. `response` or `request`.
+    pub kind: String,
+    /// The `WARC-Record-ID` header.
+    pub id: String,
+    /// The `WARC-Target-URI` header.
+    pub target_uri: String,
+    pub headers: Vec<(String, String)>,
+    pub block: Vec<u8>,
+}
+
+impl WarcRecord {
+    /// Whether the block is an HTTP message, as opposed to e.g. the `text/dns`
+    /// responses Heritrix writes for DNS lookups.
+    pub fn is_http(&self) -> bool {
+        match self.header("content-type") {
+            Some(content_type) => content_type.to_lowercase().starts_with("application/http"),
+            None => self.block.starts_with(b"HTTP/"),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP response stored in a `response` record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The body with chunked transfer and gzip/deflate content encodings undone.
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_html(&self) -> bool {
+        self.header("content-type").map(|content_type| content_type.to_lowercase())
+            .is_some_and(|content_type| content_type.contains("text/html") || content_type.contains("application/xhtml"))
+    }
+}
+
+/// Reads the records of a WARC archive, gzip-compressed (one member per
+/// record or a single one) or not.
+pub struct WarcReader {
+    reader: Box<dyn BufRead>,
+}
+
+fn read_line(reader: &mut dyn BufRead) -> Result<Option<String>, WarcError> {
+    let mut line = vec![];
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn split_header(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once(':')?;
+    Some((name.trim().to_string(), value.trim().to_string()))
+}
+
+impl WarcReader {
+    pub fn new<R: Read + 'static>(r: R) -> Result<WarcReader, WarcError> {
+        let mut reader = BufReader::new(r);
+        let gzipped = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+        let reader: Box<dyn BufRead> = if gzipped {
+            Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+        } else {
+            Box::new(reader)
+        };
+        Ok(WarcReader { reader })
+    }
+
+    fn read_record(&mut self) -> Result<Option<WarcRecord>, WarcError> {
+        // Records are followed by two CRLFs; skip them and any other blank lines.
+        let version = loop {
+            match read_line(&mut self.reader)? {
+                None => return Ok(None),
+                Some(line) if line.trim().is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+        if !version.starts_with("WARC/") {
+            return Err(WarcError::Format(format!("expected a WARC version line, got {:?}", version)));
+        }
+        let mut headers = vec![];
+        loop {
+            match read_line(&mut self.reader)? {
+                None => return Err(WarcError::Format("unexpected end of record headers".to_string())),
+                Some(line) if line.is_empty() => break,
+                Some(line) => headers.extend(split_header(&line)),
+            }
+        }
+        let mut record = WarcRecord { kind: String::new(), id: String::new(), target_uri: String::new(), headers, block: vec![] };
+        let length = record.header("content-length")
+            .and_then(|length| length.parse::<u64>().ok())
+            .ok_or_else(|| WarcError::Format("missing Content-Length".to_string()))?;
+        (&mut self.reader).take(length).read_to_end(&mut record.block)?;
+        if (record.block.len() as u64) < length {
+            return Err(WarcError::Format("truncated record block".to_string()));
+        }
+        record.kind = record.header("warc-type").unwrap_or_default().to_string();
+        record.id = record.header("warc-record-id").unwrap_or_default().to_string();
+        record.target_uri = record.header("warc-target-uri").unwrap_or_default().trim_matches(['<', '>']).to_string();
+        Ok(Some(record))
+    }
+}
+
+impl Iterator for WarcReader {
+    type Item = Result<WarcRecord, WarcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = vec![];
+    loop {
+        let end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = String::from_utf8_lossy(&body[..end]);
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[end + 2..];
+        if size == 0 {
+            return Some(out);
+        }
+        out.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Parses the HTTP response in the block of a `response` record.
+pub fn parse_http_response(block: &[u8]) -> Result<HttpResponse, WarcError> {
+    let (head, body) = match block.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => (&block[..end], &block[end + 4..]),
+        None => match block.windows(2).position(|w| w == b"\n\n") {
+            Some(end) => (&block[..end], &block[end + 2..]),
+            None => return Err(WarcError::Format("missing end of http headers".to_string())),
+        },
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+    let status = lines.next()
+        .filter(|line| line.starts_with("HTTP/"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| WarcError::Format("invalid http status line".to_string()))?;
+    let mut response = HttpResponse { status, headers: lines.filter_map(split_header).collect(), body: vec![] };
+    let chunked = response.header("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let body = if chunked { dechunk(body).unwrap_or_else(|| body.to_vec()) } else { body.to_vec() };
+    let encoding = response.header("content-encoding").unwrap_or_default().to_lowercase();
+    response.body = match encoding.as_str() {
+        "gzip" | "x-gzip" => {
+            let mut decoded = vec![];
+            GzDecoder::new(body.as_slice()).read_to_end(&mut decoded).map(|_| decoded).unwrap_or(body)
+        },
+        "deflate" => {
+            let mut decoded = vec![];
+            ZlibDecoder::new(body.as_slice()).read_to_end(&mut decoded).map(|_| decoded).unwrap_or(body)
+        },
+        _ => body,
+    };
+    Ok(response)
+}
+
+/// What parsing the html response of a WARC record gave.
+#[derive(Debug)]
+pub struct WarcFeature {
+    pub record_id: String,
+    pub url: String,
+    pub result: Result<Feature, WarcItemError>,
+}
+
+/// Runs the extractor over the successful html `response` records of a WARC
+/// archive. Other records, including non-HTTP responses, are skipped; a
+/// response whose HTTP message is malformed gives a `WarcItemError::Response`
+/// result.
+/// An `Err` item means the archive itself could not be read.
+pub struct WarcParser<'a> {
+    reader: WarcReader,
+    rules: &'a SiteRules,
+}
+
+impl<'a> Iterator for WarcParser<'a> {
+    type Item = Result<WarcFeature, WarcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.reader.next()? {
+                Ok(record) => record,
+                Err(err) => return Some(Err(err)),
+            };
+            if record.kind != "response" || !record.is_http() {
+                continue;
+            }
+            let response = match parse_http_response(&record.block) {
+                Ok(response) => response,
+                Err(err) => {
+                    let result = Err(WarcItemError::Response(err));
+                    return Some(Ok(WarcFeature { record_id: record.id, url: record.target_uri, result }));
+                },
+            };
+            if !(200..300).contains(&response.status) || !response.is_html() {
+                continue;
+            }
+            let result = crate::parse_html_with_rules(record.target_uri.clone(), &mut response.body.as_slice(),
+                response.header("content-type"), self.rules).map_err(WarcItemError::from);
+            return Some(Ok(WarcFeature { record_id: record.id, url: record.target_uri, result }));
+        }
+    }
+}
+
+/// Parses the html responses of a WARC archive read from `r`.
+pub fn parse_warc<R: Read + 'static>(r: R, rules: &SiteRules) -> Result<WarcParser<'_>, WarcError> {
+    Ok(WarcParser { reader: WarcReader::new(r)?, rules })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::{parse_http_response, parse_warc, WarcReader};
+    use crate::error::WarcItemError;
+    use crate::rules::SiteRules;
+
+    fn record(kind: &str, id: &str, uri: &str, block: &str) -> Vec<u8> {
+        let content_type = if block.starts_with("HTTP/") { "application/http; msgtype=response" } else { "text/plain" };
+        typed_record(kind, id, uri, content_type, block)
+    }
+
+    fn typed_record(kind: &str, id: &str, uri: &str, content_type: &str, block: &str) -> Vec<u8> {
+        format!("WARC/1.0\r\nWARC-Type: {}\r\nWARC-Record-ID: <urn:uuid:{}>\r\nWARC-Target-URI: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}\r\n\r\n",
+            kind, id, uri, content_type, block.len(), block).into_bytes()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_records() {
+        let html = "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<html><head><title>Hello</title></head><body><p>World</p></body></html>";
+        let mut warc = gzip(&record("warcinfo", "1", "", "software: test"));
+        warc.extend(gzip(&record("request", "2", "https://example.com/a", "GET /a HTTP/1.1\r\n\r\n")));
+        warc.extend(gzip(&record("response", "3", "https://example.com/a", html)));
+        warc.extend(gzip(&record("response", "4", "https://example.com/a.png", "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\nPNG")));
+        let records = WarcReader::new(std::io::Cursor::new(warc.clone())).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.iter().map(|record| record.kind.as_str()).collect::<Vec<_>>(), vec!["warcinfo", "request", "response", "response"]);
+        assert_eq!(records[2].target_uri, "https://example.com/a");
+
+        let rules = SiteRules::default();
+        let features = parse_warc(std::io::Cursor::new(warc), &rules).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].record_id, "<urn:uuid:3>");
+        assert_eq!(features[0].result.as_ref().unwrap().title, "Hello");
+    }
+
+    #[test]
+    fn test_skip_dns_and_report_malformed() {
+        let html = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html><head><title>Hello</title></head><body><p>World</p></body></html>";
+        let mut warc = typed_record("response", "1", "dns:example.com", "text/dns", "20220301000000\nexample.com. 300 IN A 93.184.216.34");
+        warc.extend(typed_record("response", "2", "https://example.com/broken", "application/http; msgtype=response", "garbage"));
+        warc.extend(record("response", "3", "https://example.com/a", html));
+        let rules = SiteRules::default();
+        let features = parse_warc(std::io::Cursor::new(warc), &rules).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(features.iter().map(|feature| feature.record_id.as_str()).collect::<Vec<_>>(), vec!["<urn:uuid:2>", "<urn:uuid:3>"]);
+        assert!(matches!(features[0].result, Err(WarcItemError::Response(_))));
+        assert_eq!(features[0].result.as_ref().unwrap_err().kind(), "invalid_response");
+        assert_eq!(features[1].result.as_ref().unwrap().title, "Hello");
+    }
+
+    #[test]
+    fn test_http_response() {
+        let response = parse_http_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Type: text/html\r\n\r\n5\r\nHello\r\n6\r\n world\r\n0\r\n\r\n").unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.is_html());
+        assert_eq!(response.body, b"Hello world");
+    }
+}
@@ -2,8 +2,8 @@ use std::cell::RefCell;
 use std::io;
 use std::rc::Rc;
 
-use html_dom::{NodeData, NodeDataGetter, Document};
-use html_dom::custom_parse_document;
+use html_dom::{NodeData, NodeDataGetter, Document, Error};
+use html_dom::custom_parse_document_with_charset;
 
 use crate::style::{StyleInfo, parse_style_info};
 
@@ -77,9 +77,14 @@ fn parse_vision(s: &str) -> Option<Vision> {
     }
 }
 
-pub fn parse_document<R>(r: &mut R) -> Result<Document<RenderNodeData>, String>
+pub fn parse_document<R>(r: &mut R) -> Result<Document<RenderNodeData>, Error>
 where R: io::Read {
-    let doc = custom_parse_document::<R, RenderNodeData>(r)?;
+    parse_document_with_charset(r, None)
+}
+
+pub fn parse_document_with_charset<R>(r: &mut R, content_type: Option<&str>) -> Result<Document<RenderNodeData>, Error>
+where R: io::Read {
+    let doc = custom_parse_document_with_charset::<R, RenderNodeData>(r, content_type)?;
     let id = Rc::new(RefCell::new(0 as u32));
     doc.document.borrow_mut().trace(|node| {
         *id.borrow_mut() += 1;
@@ -5,8 +5,17 @@ mod style;
 extern crate num_derive;
 
 pub use dom::parse_document;
+pub use dom::parse_document_with_charset;
 pub use dom::RenderNodeData;
+pub use dom::Vision;
+pub use style::StyleInfo;
+pub use style::{Display, Float, Overflow, Position, TextAlign};
+pub use style::{Edges, Rgb};
 pub type Handle = html_dom::Handle<RenderNodeData>;
+pub type Node = html_dom::Node<RenderNodeData>;
+pub use html_dom::Error as DomError;
+pub use html_dom::new_handle;
+pub use html_dom::Selector;
 
 #[cfg(test)]
 mod tests {
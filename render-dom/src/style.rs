@@ -36,7 +36,7 @@ impl<'a> Into<u32> for Value<'a> {
         self.0.parse::<>().unwrap_or_default()
     }
 }
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(FromPrimitive, ToPrimitive, Clone, Copy, PartialEq, Debug)]
 pub enum Display {
     Inline = 0,
     None = 1,
@@ -71,7 +71,7 @@ impl Default for Display {
     }
 }
 
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(FromPrimitive, ToPrimitive, Clone, Copy, PartialEq, Debug)]
 pub enum Float {
     None = 0,
     Left = 1,
@@ -91,7 +91,7 @@ impl Default for Float {
     }
 }
 
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(FromPrimitive, ToPrimitive, Clone, Copy, PartialEq, Debug)]
 pub enum Overflow {
     Visible = 0,
     Hidden = 1,
@@ -113,7 +113,7 @@ impl Default for Overflow {
     }
 }
 
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(FromPrimitive, ToPrimitive, Clone, Copy, PartialEq, Debug)]
 pub enum Position {
     Static = 0,
     Absolute = 1,
@@ -134,7 +134,7 @@ impl Default for Position {
     }
 }
 
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(FromPrimitive, ToPrimitive, Clone, Copy, PartialEq, Debug)]
 pub enum TextAlign {
     Left = 0,
     Right = 1,
@@ -203,6 +203,125 @@ impl Default for StyleInfo {
     }
 }
 
+/// A color as decoded from the packed `0xRRGGBB` value of `dom_style_info`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// Widths of the four sides of a padding or border box, in pixels.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Edges {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Edges {
+    pub fn horizontal(&self) -> i32 {
+        self.left + self.right
+    }
+
+    pub fn vertical(&self) -> i32 {
+        self.top + self.bottom
+    }
+}
+
+impl StyleInfo {
+    pub fn font_size(&self) -> i32 {
+        self.font_size
+    }
+
+    pub fn font_weight(&self) -> i32 {
+        self.font_weight
+    }
+
+    pub fn display(&self) -> Display {
+        self.display
+    }
+
+    pub fn float(&self) -> Float {
+        self.float_
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn overflow_x(&self) -> Overflow {
+        self.overflow_x
+    }
+
+    pub fn overflow_y(&self) -> Overflow {
+        self.overflow_y
+    }
+
+    pub fn color(&self) -> Rgb {
+        Rgb {
+            red: (self.color >> 16 & 0xff) as u8,
+            green: (self.color >> 8 & 0xff) as u8,
+            blue: (self.color & 0xff) as u8,
+        }
+    }
+
+    pub fn text_align(&self) -> TextAlign {
+        self.text_align
+    }
+
+    pub fn text_indent(&self) -> i32 {
+        self.text_indent
+    }
+
+    pub fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    pub fn padding(&self) -> Edges {
+        Edges {
+            top: self.padding_top,
+            right: self.padding_right,
+            bottom: self.padding_bottom,
+            left: self.padding_left,
+        }
+    }
+
+    pub fn border(&self) -> Edges {
+        Edges {
+            top: self.border_top_width,
+            right: self.border_right_width,
+            bottom: self.border_bottom_width,
+            left: self.border_left_width,
+        }
+    }
+
+    /// Bold text in the CSS sense, `font-weight` of 600 or more.
+    pub fn is_bold(&self) -> bool {
+        self.font_weight >= 600
+    }
+
+    /// Whether the element generates no box at all.
+    pub fn is_hidden(&self) -> bool {
+        self.display == Display::None
+    }
+
+    /// Whether the element lays out as a block, list item or table part rather
+    /// than inline. Elements without style info report `Display::Inline`.
+    pub fn is_block_level(&self) -> bool {
+        !matches!(self.display, Display::Inline | Display::None | Display::InlineBlock
+            | Display::InlineTable | Display::Marker | Display::Inherit)
+    }
+
+    /// Whether the element is taken out of the normal flow by absolute or fixed
+    /// positioning or by floating.
+    pub fn is_positioned_out_of_flow(&self) -> bool {
+        matches!(self.position, Position::Absolute | Position::Fixed)
+            || matches!(self.float_, Float::Left | Float::Right)
+    }
+}
+
 macro_rules! parse_style {
     ($v: ident, $items: ident, $($name: ident), +) => {
         $(
@@ -237,3 +356,48 @@ pub fn parse_style_info(s: &str, style: &mut StyleInfo) {
         z_index
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_style_info, Display, Edges, Float, Position, Rgb, StyleInfo};
+
+    #[test]
+    fn test_parse_style_info() {
+        let mut items = vec!["0"; 51];
+        items[8] = "1";
+        items[17] = "2";
+        items[22] = "18";
+        items[24] = "700";
+        items[31] = "10";
+        items[32] = "10";
+        items[39] = "2";
+        items[40] = "1";
+        items[43] = "1";
+        items[44] = "16711808";
+        items[50] = "-1";
+        let mut style = StyleInfo::default();
+        parse_style_info(&items.join(";"), &mut style);
+
+        assert_eq!(style.font_size(), 18);
+        assert!(style.is_bold());
+        assert_eq!(style.display(), Display::Block);
+        assert_eq!(style.float(), Float::Left);
+        assert_eq!(style.position(), Position::Absolute);
+        assert_eq!(style.color(), Rgb { red: 255, green: 0, blue: 128 });
+        assert_eq!(style.z_index(), -1);
+        assert_eq!(style.padding(), Edges { top: 0, right: 10, bottom: 0, left: 10 });
+        assert_eq!(style.border(), Edges { top: 2, right: 0, bottom: 1, left: 0 });
+        assert!(style.is_block_level());
+        assert!(style.is_positioned_out_of_flow());
+        assert!(!style.is_hidden());
+    }
+
+    #[test]
+    fn test_default_style() {
+        let mut style = StyleInfo::default();
+        assert!(!style.is_block_level());
+        parse_style_info("1;2;3", &mut style);
+        assert_eq!(style.display(), Display::Inline);
+        assert!(!style.is_positioned_out_of_flow());
+    }
+}